@@ -0,0 +1,53 @@
+#!/usr/bin/env python3
+# Generate the default sound pack, the sounds are synthesized here so they are
+# free of any third party license. Run from this directory.
+import math
+import struct
+import wave
+
+RATE = 22050
+
+
+def pluck(freq, duration, sweep=1.0):
+    samples = []
+    phase = 0.0
+    count = int(duration * RATE)
+    for i in range(count):
+        t = i / RATE
+        progress = i / count
+        phase += 2 * math.pi * freq * sweep**progress / RATE
+        # a short attack avoids the click at the start
+        envelope = min(t / 0.004, 1.0) * math.exp(-t * 8.0 / duration)
+        sample = math.sin(phase) + 0.3 * math.sin(2 * phase)
+        samples.append(sample * envelope / 1.3)
+    return samples
+
+
+def sequence(notes, step, duration):
+    total = int((step * (len(notes) - 1) + duration) * RATE)
+    samples = [0.0] * total
+    for i, freq in enumerate(notes):
+        start = int(i * step * RATE)
+        for j, sample in enumerate(pluck(freq, duration)):
+            samples[start + j] += sample
+    peak = max(abs(x) for x in samples)
+    return [x / peak for x in samples]
+
+
+def write(name, samples, volume=0.7):
+    with wave.open(name, "wb") as file:
+        file.setnchannels(1)
+        file.setsampwidth(2)
+        file.setframerate(RATE)
+        file.writeframes(
+            b"".join(struct.pack("<h", int(x * volume * 32767)) for x in samples)
+        )
+
+
+C5, E5, G5, C6, D6, A4, E6 = 523.25, 659.25, 783.99, 1046.5, 1174.66, 440.0, 1318.51
+write("startup.wav", sequence([C5, E5, G5, C6], 0.09, 0.4))
+write("shutdown.wav", sequence([C6, G5, E5, C5], 0.09, 0.4))
+write("open.wav", sequence([G5, D6], 0.08, 0.3))
+write("close.wav", sequence([D6, G5], 0.08, 0.3))
+write("add.wav", pluck(E6, 0.12, 1.2), 0.5)
+write("remove.wav", pluck(A4, 0.12, 0.8), 0.5)
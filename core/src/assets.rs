@@ -0,0 +1,29 @@
+use std::path::Path;
+
+/// Used when there is no config file
+pub const DEFAULT_CONFIG: &str = include_str!("../assets/default.yml");
+
+// the files in the config starting with this are read from the plugin
+const PREFIX: &str = "builtin:";
+
+const FILES: &[(&str, &[u8])] = &[
+    ("startup.wav", include_bytes!("../assets/startup.wav")),
+    ("shutdown.wav", include_bytes!("../assets/shutdown.wav")),
+    ("open.wav", include_bytes!("../assets/open.wav")),
+    ("close.wav", include_bytes!("../assets/close.wav")),
+    ("add.wav", include_bytes!("../assets/add.wav")),
+    ("remove.wav", include_bytes!("../assets/remove.wav")),
+];
+
+pub fn is_builtin(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with(PREFIX))
+}
+
+/// The content of a built-in file, eg: `builtin:startup.wav`
+pub fn get(path: &Path) -> Option<&'static [u8]> {
+    let name = path.to_str()?.strip_prefix(PREFIX)?;
+    FILES
+        .iter()
+        .find(|(file, _data)| *file == name)
+        .map(|(_file, data)| *data)
+}
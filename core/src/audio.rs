@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+use rodio::Source;
+
+use crate::cache::SampleCache;
+use crate::config::AudioConfig;
+use crate::event::{Event, EventArgs, EventKind};
+use crate::playback::{PlaybackParams, Sound};
+use crate::scheduler::Voice;
+use crate::sink::{EventSink, Interest};
+
+/// The loaded sounds of each event
+#[derive(Clone, Default)]
+pub struct AudioFiles {
+    sounds: BTreeMap<EventKind, Sound>,
+}
+
+impl AudioFiles {
+    pub fn load(config: &AudioConfig, cache: &mut SampleCache) -> Result<Self> {
+        let mut sounds = BTreeMap::new();
+        for (event, config) in config.iter() {
+            let sound = Sound::load(config, cache)
+                .with_context(|| format!("Invalid config for {event}"))?;
+            sounds.insert(event, sound);
+        }
+        Ok(Self { sounds })
+    }
+
+    pub fn get(&self, event: EventKind) -> Option<&Sound> {
+        self.sounds.get(&event)
+    }
+
+    /// Replace the sound of the event
+    pub fn set(&mut self, event: EventKind, sound: Sound) {
+        self.sounds.insert(event, sound);
+    }
+
+    /// Don't play a sound for the event
+    pub fn remove(&mut self, event: EventKind) {
+        self.sounds.remove(&event);
+    }
+
+    /// The sound to play for the event, if it passes the filter and the
+    /// scheduler. The args are only decoded if there is a filter or mappings
+    /// for the event.
+    pub fn admit(
+        &self,
+        event: EventKind,
+        now: Instant,
+        volume: f32,
+        args: impl FnOnce() -> EventArgs,
+    ) -> Option<PendingSound> {
+        log_dbg!(&format!("Audio callback for: {event}"));
+        let sound = self.get(event)?;
+        let args = sound.needs_args().then(args);
+        if let (Some(filter), Some(args)) = (&sound.filter, &args)
+            && !filter.matches(args)
+        {
+            log_dbg!(&format!("Audio filtered out: {event}"));
+            return None;
+        }
+        if !sound.scheduler.admit(now) {
+            log_dbg!(&format!("Audio dropped by the scheduler: {event}"));
+            return None;
+        }
+        let params = sound.params(args.as_ref());
+        Some(PendingSound {
+            event,
+            sound: sound.clone(),
+            params,
+            volume,
+        })
+    }
+}
+
+/// A sound ready to play, only decoded if the sink needs the audio
+pub struct PendingSound {
+    event: EventKind,
+    sound: Sound,
+    params: PlaybackParams,
+    volume: f32,
+}
+
+impl PendingSound {
+    /// Play the event sound, ignoring the filter and scheduler
+    pub fn new(event: EventKind, sound: Sound, volume: f32) -> Self {
+        Self {
+            event,
+            params: sound.params,
+            sound,
+            volume,
+        }
+    }
+
+    /// Same as new, but with other params, eg: set by a script
+    pub fn with_params(
+        event: EventKind,
+        sound: Sound,
+        params: PlaybackParams,
+        volume: f32,
+    ) -> Self {
+        Self {
+            event,
+            sound,
+            params,
+            volume,
+        }
+    }
+
+    pub fn event(&self) -> EventKind {
+        self.event
+    }
+
+    pub fn decode(self) -> Option<Voice<impl Source + Send + 'static>> {
+        let event = self.event;
+        log_dbg!(&format!("Play audio file: {event}"));
+        match self.sound.pool.pick().source() {
+            Ok(source) => {
+                let source = self.params.apply(source).amplify(self.volume);
+                Some(self.sound.scheduler.track(source))
+            }
+            Err(e) => {
+                err!(&format!("Unable to decode audio for {event}: {e}"));
+                None
+            }
+        }
+    }
+}
+
+/// Where the sounds end up, eg: the audio device
+pub trait AudioSink: Send + Sync {
+    fn play(&self, sound: PendingSound);
+}
+
+/// Play the sounds of the events
+pub struct AudioEventSink {
+    audio: Arc<AudioFiles>,
+    output: Arc<dyn AudioSink>,
+    volume: f32,
+}
+
+impl AudioEventSink {
+    pub fn new(
+        audio: Arc<AudioFiles>,
+        output: Arc<dyn AudioSink>,
+        volume: f32,
+    ) -> Self {
+        Self {
+            audio,
+            output,
+            volume,
+        }
+    }
+}
+
+impl EventSink for AudioEventSink {
+    fn interest(&self, kind: EventKind) -> Interest {
+        match self.audio.get(kind) {
+            None => Interest::None,
+            Some(sound) if sound.needs_args() => Interest::Args,
+            Some(_) => Interest::Kind,
+        }
+    }
+
+    fn handle(&self, event: &Event) {
+        let Some(sound) =
+            self.audio
+                .admit(event.kind(), Instant::now(), self.volume, || {
+                    event.args().clone()
+                })
+        else {
+            return;
+        };
+        self.output.play(sound);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    use crate::cache::CacheConfig;
+
+    fn load(yaml: &str) -> AudioFiles {
+        let config: AudioConfig = serde_yaml::from_str(yaml).unwrap();
+        let mut cache = SampleCache::new(CacheConfig::default());
+        AudioFiles::load(&config, &mut cache).unwrap()
+    }
+
+    #[test]
+    fn admit_applies_filter_and_mappings() {
+        let audio = load(
+            "
+            symbol_added:
+              synth: {freq: 440}
+              filter: {name: ^mem}
+              map:
+                - {from: len, to: volume, input: [0, 10], output: [0, 1]}
+            ",
+        );
+        let now = Instant::now();
+        let args = |name: &str| {
+            let name = name.to_string();
+            move || EventArgs {
+                name: Some(name),
+                len: Some(5),
+                ..EventArgs::default()
+            }
+        };
+        let event = EventKind::SymbolAdded;
+        assert!(audio.admit(event, now, 1.0, args("strcpy")).is_none());
+        let sound = audio.admit(event, now, 1.0, args("memcpy")).unwrap();
+        assert_eq!(sound.params.volume, Some(0.5));
+        assert!(
+            audio
+                .admit(EventKind::SymbolRemoved, now, 1.0, EventArgs::default)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn args_only_decoded_if_needed() {
+        let audio = load("function_added: {synth: {freq: 440}}");
+        let sound =
+            audio.admit(EventKind::FunctionAdded, Instant::now(), 1.0, || {
+                unreachable!("the args are not used")
+            });
+        assert!(sound.is_some());
+    }
+
+    #[test]
+    fn decode_plays_the_synth() {
+        let audio = load("function_added: {synth: {freq: 440}}");
+        let sound = audio
+            .admit(
+                EventKind::FunctionAdded,
+                Instant::now(),
+                1.0,
+                EventArgs::default,
+            )
+            .unwrap();
+        assert_eq!(sound.event(), EventKind::FunctionAdded);
+        let samples: Vec<f32> = sound.decode().unwrap().collect();
+        assert!(!samples.is_empty());
+        assert!(samples.iter().any(|sample| *sample != 0.0));
+    }
+
+    #[derive(Default)]
+    struct Played(Mutex<Vec<EventKind>>);
+
+    impl AudioSink for Played {
+        fn play(&self, sound: PendingSound) {
+            self.0.lock().unwrap().push(sound.event());
+        }
+    }
+
+    #[test]
+    fn sink_plays_the_admitted_events() {
+        let audio = Arc::new(load(
+            "
+            function_added: {synth: {freq: 440}}
+            symbol_added: {synth: {freq: 440}, filter: {name: ^mem}}
+            ",
+        ));
+        let played = Arc::new(Played::default());
+        let sink = AudioEventSink::new(audio, played.clone(), 1.0);
+        assert_eq!(sink.interest(EventKind::FunctionAdded), Interest::Kind);
+        assert_eq!(sink.interest(EventKind::SymbolAdded), Interest::Args);
+        assert_eq!(sink.interest(EventKind::TagAdded), Interest::None);
+        let event = Event::without_args;
+        sink.handle(&event(EventKind::FunctionAdded));
+        // filtered out, there is no name
+        sink.handle(&event(EventKind::SymbolAdded));
+        assert_eq!(*played.0.lock().unwrap(), [EventKind::FunctionAdded]);
+    }
+}
@@ -0,0 +1,102 @@
+//! The `binjuice-replay` tool, see binjuice_core::replay
+
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use binjuice_core::replay::{ReplayOptions, replay};
+
+const USAGE: &str = "\
+Usage: binjuice-replay [OPTIONS] <TRACE>
+
+Play the events of a BinJuice trace file, using the sounds of the config
+
+Options:
+  --config <FILE>     config file [default: binjuice.yml]
+  --packs <DIR>       directory with the sound packs used by the config
+  --profile <NAME>    use this profile instead of the config one
+  --speed <FACTOR>    replay speed, 2.0 is twice as fast [default: 1.0]
+  --max-gap <SECONDS> shorten the silences longer than this [default: 5.0]
+  --session <ID>      only replay the events of this view session
+  --device <NAME>     play on this audio device instead of the config one
+  --wav <FILE>        write the sounds to a WAV file instead of playing them
+  --help              show this message
+";
+
+struct Args {
+    trace: PathBuf,
+    options: ReplayOptions,
+}
+
+impl Args {
+    /// None if only the usage was requested
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>> {
+        let mut trace = None;
+        let mut options = ReplayOptions::default();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing the value of {arg}"))
+            };
+            match arg.as_str() {
+                "--config" => options.config = value()?.into(),
+                "--packs" => options.packs = Some(value()?.into()),
+                "--profile" => options.profile = Some(value()?),
+                "--speed" => {
+                    options.speed = value()?.parse().context("Invalid speed")?
+                }
+                "--max-gap" => {
+                    options.max_gap =
+                        value()?.parse().context("Invalid max gap")?
+                }
+                "--session" => {
+                    options.session =
+                        Some(value()?.parse().context("Invalid session")?)
+                }
+                "--device" => options.device = Some(value()?),
+                "--wav" => options.wav = Some(value()?.into()),
+                "-h" | "--help" => return Ok(None),
+                _ if arg.starts_with('-') => bail!("Unknown option {arg}"),
+                _ if trace.is_none() => trace = Some(arg.into()),
+                _ => bail!("Unexpected argument {arg}"),
+            }
+        }
+        options.check()?;
+        Ok(Some(Self {
+            trace: trace.context("Missing the trace file")?,
+            options,
+        }))
+    }
+}
+
+fn main() -> Result<()> {
+    let Some(args) = Args::parse(std::env::args().skip(1))? else {
+        print!("{USAGE}");
+        return Ok(());
+    };
+    replay(&args.trace, args.options)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn parse_args() {
+        let args =
+            |args: &[&str]| Args::parse(args.iter().map(|arg| arg.to_string()));
+        let parsed = args(&["--speed", "2", "--wav", "a.wav", "t.jsonl"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.options.speed, 2.0);
+        assert_eq!(parsed.trace, Path::new("t.jsonl"));
+        assert_eq!(parsed.options.config, Path::new("binjuice.yml"));
+        assert!(args(&["--help"]).unwrap().is_none());
+        assert!(args(&["--speed", "0", "t.jsonl"]).is_err());
+        assert!(args(&["--speed"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+        assert!(args(&[]).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use rodio::buffer::SamplesBuffer;
+use rodio::{Decoder, Source};
+
+use serde::{Deserialize, Serialize};
+
+/// Controls what is kept decoded in memory and what is streamed from disk
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// clips longer than this are streamed from disk
+    pub max_decoded_ms: u64,
+    /// once all the decoded clips reach this size, the rest is streamed
+    pub memory_budget_mb: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_decoded_ms: 10_000,
+            memory_budget_mb: 64,
+        }
+    }
+}
+
+/// A clip ready to play, either already decoded or the file it's streamed from
+#[derive(Clone)]
+pub enum ClipData {
+    Decoded(SamplesBuffer),
+    Streamed(PathBuf),
+}
+
+impl ClipData {
+    pub fn source(&self) -> Result<Box<dyn Source + Send>> {
+        match self {
+            // the samples are shared, so cloning the buffer is cheap
+            ClipData::Decoded(buffer) => Ok(Box::new(buffer.clone())),
+            ClipData::Streamed(path) => {
+                let file = File::open(path).with_context(|| {
+                    format!("Unable to open {}", path.display())
+                })?;
+                Ok(Box::new(Decoder::try_from(file)?))
+            }
+        }
+    }
+}
+
+/// Decode each file once, even if used by multiple events
+pub struct SampleCache {
+    config: CacheConfig,
+    used_bytes: usize,
+    clips: HashMap<PathBuf, ClipData>,
+}
+
+impl SampleCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            used_bytes: 0,
+            clips: HashMap::new(),
+        }
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<ClipData> {
+        if let Some(clip) = self.clips.get(path) {
+            return Ok(clip.clone());
+        }
+        if let Some(data) = crate::assets::get(path) {
+            return self.load_builtin(path, data);
+        }
+        let file = File::open(path)
+            .with_context(|| format!("Unable to open {}", path.display()))?;
+        let decoder = Decoder::try_from(file)
+            .with_context(|| format!("Unable to decode {}", path.display()))?;
+        let clip = match self.decode(decoder) {
+            Some(buffer) => ClipData::Decoded(buffer),
+            None => ClipData::Streamed(path.to_owned()),
+        };
+        self.clips.insert(path.to_owned(), clip.clone());
+        Ok(clip)
+    }
+
+    // the built-in files are small, so they are always decoded
+    fn load_builtin(
+        &mut self,
+        path: &Path,
+        data: &'static [u8],
+    ) -> Result<ClipData> {
+        let decoder = Decoder::new(Cursor::new(data))
+            .with_context(|| format!("Unable to decode {}", path.display()))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let samples: Vec<_> = decoder.collect();
+        self.used_bytes += samples.len() * std::mem::size_of::<f32>();
+        let clip = ClipData::Decoded(SamplesBuffer::new(
+            channels,
+            sample_rate,
+            samples,
+        ));
+        self.clips.insert(path.to_owned(), clip.clone());
+        Ok(clip)
+    }
+
+    // decode the whole clip, unless it is too long or does not fit the budget
+    fn decode<S: Source>(&mut self, source: S) -> Option<SamplesBuffer> {
+        let max_duration = Duration::from_millis(self.config.max_decoded_ms);
+        if source.total_duration().is_some_and(|x| x > max_duration) {
+            return None;
+        }
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let max_samples = (max_duration.as_secs_f64()
+            * f64::from(sample_rate)
+            * f64::from(channels)) as usize;
+        let budget = self
+            .config
+            .memory_budget_mb
+            .saturating_mul(1 << 20)
+            .saturating_sub(self.used_bytes);
+        let max_samples = max_samples.min(budget / std::mem::size_of::<f32>());
+
+        let mut samples = vec![];
+        for sample in source {
+            if samples.len() >= max_samples {
+                return None;
+            }
+            samples.push(sample);
+        }
+        self.used_bytes += samples.len() * std::mem::size_of::<f32>();
+        Some(SamplesBuffer::new(channels, sample_rate, samples))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(max_decoded_ms: u64, memory_budget_mb: usize) -> SampleCache {
+        SampleCache::new(CacheConfig {
+            max_decoded_ms,
+            memory_budget_mb,
+        })
+    }
+
+    fn clip(len: usize) -> SamplesBuffer {
+        SamplesBuffer::new(1, 44100, vec![0.0; len])
+    }
+
+    #[test]
+    fn budget_cutoff() {
+        let mut cache = cache(60_000, 1);
+        // 1 MB is 262144 samples
+        assert!(cache.decode(clip(200_000)).is_some());
+        assert_eq!(cache.used_bytes, 800_000);
+        // only what is left of the budget
+        assert!(cache.decode(clip(100_000)).is_none());
+        assert!(cache.decode(clip(50_000)).is_some());
+    }
+
+    #[test]
+    fn huge_budget() {
+        // more than the address space, so it's the same as no budget
+        let mut cache = cache(1000, usize::MAX);
+        assert!(cache.decode(clip(1000)).is_some());
+    }
+
+    #[test]
+    fn long_clips_are_streamed() {
+        let mut cache = cache(1000, 64);
+        assert!(cache.decode(clip(44_100)).is_some());
+        assert!(cache.decode(clip(44_101)).is_none());
+    }
+
+    #[test]
+    fn decoded_once() {
+        let mut cache = cache(1000, 64);
+        let path = Path::new("builtin:startup.wav");
+        cache.load(path).unwrap();
+        let used_bytes = cache.used_bytes;
+        assert!(used_bytes > 0);
+        assert!(matches!(cache.load(path).unwrap(), ClipData::Decoded(_)));
+        assert_eq!(cache.used_bytes, used_bytes);
+    }
+}
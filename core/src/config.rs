@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::assets;
+use crate::cache::CacheConfig;
+use crate::event::EventKind;
+use crate::midi::MidiConfig;
+use crate::osc::OscConfig;
+use crate::output::FallbackConfig;
+use crate::pack;
+use crate::playback::SoundConfig;
+use crate::profile::ProfileConfig;
+use crate::synth;
+
+#[derive(Deserialize, Serialize)]
+pub struct Config {
+    /// name of the sound pack at the packs directory
+    pub pack: Option<String>,
+    /// the active profile, the top level files if not set
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub files: AudioConfig,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// use the built-in synth sounds for the events missing from files
+    #[serde(default)]
+    pub synth_preset: bool,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    /// write all the events received to this JSONL file
+    pub trace: Option<PathBuf>,
+    /// name of the audio device, the default device if not set
+    pub device: Option<String>,
+    /// only used at startup, if the audio device can't be opened
+    #[serde(default)]
+    pub fallback: FallbackConfig,
+    /// also send the events as OSC messages
+    pub osc: Option<OscConfig>,
+    /// also play the events as MIDI notes
+    pub midi: Option<MidiConfig>,
+}
+
+impl Config {
+    /// The config used when there is no config file
+    pub fn builtin() -> Self {
+        serde_yaml::from_str(assets::DEFAULT_CONFIG)
+            .expect("the default config is valid")
+    }
+
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path)
+            .context("Unable to open the config file")?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// Add the sounds from the pack and synth preset to the files
+    pub fn merge_sounds(&mut self, packs_dir: &Path) -> Result<()> {
+        if let Some(name) = &self.pack {
+            let pack = pack::load(packs_dir, name)
+                .with_context(|| format!("Unable to load sound pack {name}"))?;
+            // the config file takes priority over the pack
+            self.files.merge(pack.files);
+        }
+        if self.synth_preset {
+            self.files.merge(AudioConfig::synth_preset());
+        }
+        Ok(())
+    }
+}
+
+/// The sound config of each event
+#[derive(Clone, Default, Serialize)]
+#[serde(transparent)]
+pub struct AudioConfig(BTreeMap<EventKind, SoundConfig>);
+
+// an empty entry is the same as a missing one
+impl<'de> Deserialize<'de> for AudioConfig {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let files = BTreeMap::<EventKind, Option<SoundConfig>>::deserialize(
+            deserializer,
+        )?;
+        Ok(Self(
+            files
+                .into_iter()
+                .filter_map(|(event, config)| Some((event, config?)))
+                .collect(),
+        ))
+    }
+}
+
+impl AudioConfig {
+    pub fn get(&self, event: EventKind) -> Option<&SoundConfig> {
+        self.0.get(&event)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EventKind, &SoundConfig)> {
+        self.0.iter().map(|(event, config)| (*event, config))
+    }
+
+    /// Use the sounds from base for the events missing here
+    pub fn merge(&mut self, base: AudioConfig) {
+        for (event, config) in base.0 {
+            self.0.entry(event).or_insert(config);
+        }
+    }
+
+    /// Remove the events matching the pattern, false if none matched
+    pub fn mute(&mut self, pattern: &glob::Pattern) -> bool {
+        let mut matched = false;
+        for event in EventKind::ALL {
+            if pattern.matches(event.name()) {
+                self.0.remove(event);
+                matched = true;
+            }
+        }
+        matched
+    }
+
+    /// The built-in synth sounds for all the events
+    pub fn synth_preset() -> Self {
+        Self(
+            EventKind::ALL
+                .iter()
+                .map(|event| (*event, synth::preset(*event)))
+                .collect(),
+        )
+    }
+
+    /// Make the relative paths relative to `base`
+    pub fn resolve_paths(&mut self, base: &Path) {
+        for config in self.0.values_mut() {
+            config.resolve_paths(base);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::audio::AudioFiles;
+    use crate::cache::SampleCache;
+
+    fn parse(yaml: &str) -> AudioConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn path(config: &AudioConfig, event: EventKind) -> Option<&Path> {
+        match config.get(event)? {
+            SoundConfig::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn parse_all_forms() {
+        let config = parse(
+            "
+            function_added: a.flac
+            function_removed: [a.flac, {path: b.flac, weight: 2.0}]
+            function_updated: {path: c.flac, volume: 0.5, cooldown_ms: 10}
+            symbol_added:
+            ",
+        );
+        assert_eq!(config.iter().count(), 3);
+        assert!(matches!(
+            config.get(EventKind::FunctionRemoved),
+            Some(SoundConfig::Pool(pool)) if pool.len() == 2
+        ));
+        let updated = config.get(EventKind::FunctionUpdated).unwrap();
+        assert_eq!(updated.params().volume, Some(0.5));
+        assert_eq!(updated.schedule().cooldown_ms, Some(10));
+        assert!(config.get(EventKind::SymbolAdded).is_none());
+    }
+
+    #[test]
+    fn unknown_event() {
+        let result = serde_yaml::from_str::<AudioConfig>("functon_added: a");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn merge_keeps_own_events() {
+        let mut config = parse("function_added: a.flac");
+        config.merge(parse("{function_added: b.flac, tag_added: c.flac}"));
+        let added = path(&config, EventKind::FunctionAdded);
+        assert_eq!(added, Some(Path::new("a.flac")));
+        let tag = path(&config, EventKind::TagAdded);
+        assert_eq!(tag, Some(Path::new("c.flac")));
+    }
+
+    #[test]
+    fn mute_by_pattern() {
+        let mut config = parse(
+            "{function_added: a, function_updated: b, symbol_updated: c}",
+        );
+        assert!(config.mute(&glob::Pattern::new("*_updated").unwrap()));
+        assert!(!config.mute(&glob::Pattern::new("nothing").unwrap()));
+        let events: Vec<_> = config.iter().map(|(event, _)| event).collect();
+        assert_eq!(events, [EventKind::FunctionAdded]);
+    }
+
+    #[test]
+    fn resolve_relative_paths() {
+        let mut config = parse(
+            "{function_added: a.flac, tag_added: builtin:add.wav, \
+            symbol_added: /abs.flac}",
+        );
+        config.resolve_paths(Path::new("/pack"));
+        let resolved = |event| path(&config, event).unwrap().to_owned();
+        assert_eq!(
+            resolved(EventKind::FunctionAdded),
+            Path::new("/pack/a.flac")
+        );
+        assert_eq!(resolved(EventKind::TagAdded), Path::new("builtin:add.wav"));
+        assert_eq!(resolved(EventKind::SymbolAdded), Path::new("/abs.flac"));
+    }
+
+    #[test]
+    fn builtin_config_is_valid() {
+        let mut config = Config::builtin();
+        assert!(config.files.get(EventKind::StartBinaryNinja).is_some());
+        config.merge_sounds(Path::new("")).unwrap();
+        let mut cache = SampleCache::new(config.cache);
+        AudioFiles::load(&config.files, &mut cache).unwrap();
+    }
+}
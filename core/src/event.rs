@@ -0,0 +1,565 @@
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
+
+use paste::paste;
+
+use serde::{Deserialize, Serialize};
+
+use crate::filter::StringKind;
+
+macro_rules! events {
+    ($($name:ident),* $(,)?) => {
+        paste! {
+            /// All the events BinJuice can play a sound for
+            #[derive(
+                Clone,
+                Copy,
+                Debug,
+                PartialEq,
+                Eq,
+                PartialOrd,
+                Ord,
+                Hash,
+                Deserialize,
+                Serialize,
+            )]
+            #[serde(rename_all = "snake_case")]
+            pub enum EventKind {
+                $([<$name:camel>],)*
+            }
+
+            impl EventKind {
+                pub const ALL: &[EventKind] = &[
+                    $(EventKind::[<$name:camel>],)*
+                ];
+
+                /// The name used in the config, eg: `function_added`
+                pub fn name(self) -> &'static str {
+                    match self {
+                        $(EventKind::[<$name:camel>] => stringify!($name),)*
+                    }
+                }
+            }
+
+        }
+    };
+}
+
+events! {
+    // sent by BinJuice itself, not a notification
+    start_binary_ninja,
+    end_binary_ninja,
+    start_binary_view,
+    end_binary_view,
+    // the DataNotification callbacks
+    notification_barrier,
+    data_written,
+    data_inserted,
+    data_removed,
+    function_added,
+    function_removed,
+    function_updated,
+    function_update_requested,
+    data_variable_added,
+    data_variable_removed,
+    data_variable_updated,
+    data_metadata_updated,
+    tag_type_updated,
+    tag_added,
+    tag_removed,
+    tag_updated,
+    symbol_added,
+    symbol_removed,
+    symbol_updated,
+    string_found,
+    string_removed,
+    type_defined,
+    type_undefined,
+    type_reference_changed,
+    type_field_reference_changed,
+    segment_added,
+    segment_removed,
+    segment_updated,
+    section_added,
+    section_removed,
+    section_updated,
+    component_name_updated,
+    component_added,
+    component_moved,
+    component_removed,
+    component_function_added,
+    component_function_removed,
+    component_data_variable_added,
+    component_data_variable_removed,
+    external_library_added,
+    external_library_updated,
+    external_library_removed,
+    external_location_added,
+    external_location_updated,
+    external_location_removed,
+    type_archive_attached,
+    type_archive_detached,
+    type_archive_connected,
+    type_archive_disconnected,
+    undo_entry_added,
+    undo_entry_taken,
+    redo_entry_taken,
+    rebased,
+}
+
+impl EventKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|event| event.name() == name)
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A function, as sent by the function events
+#[derive(Clone, Debug, PartialEq)]
+pub struct FunctionInfo {
+    pub address: u64,
+    pub name: String,
+    pub size: u64,
+    pub basic_blocks: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolInfo {
+    pub address: u64,
+    pub name: String,
+}
+
+/// A tag at an address
+#[derive(Clone, Debug, PartialEq)]
+pub struct TagInfo {
+    pub address: u64,
+    pub tag_type: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StringInfo {
+    pub address: u64,
+    pub len: u64,
+    pub string_type: StringKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentInfo {
+    pub range: Range<u64>,
+    /// read = 4, write = 2 and execute = 1
+    pub permissions: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionInfo {
+    pub range: Range<u64>,
+    pub name: String,
+}
+
+/// An event received by BinJuice, with the decoded arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum BinJuiceEvent {
+    StartBinaryNinja,
+    EndBinaryNinja,
+    StartBinaryView,
+    EndBinaryView,
+    NotificationBarrier,
+    DataWritten { address: u64, len: u64 },
+    DataInserted { address: u64, len: u64 },
+    DataRemoved { address: u64, len: u64 },
+    FunctionAdded(FunctionInfo),
+    FunctionRemoved(FunctionInfo),
+    FunctionUpdated(FunctionInfo),
+    FunctionUpdateRequested(FunctionInfo),
+    DataVariableAdded { address: u64 },
+    DataVariableRemoved { address: u64 },
+    DataVariableUpdated { address: u64 },
+    DataMetadataUpdated { address: u64 },
+    TagTypeUpdated { tag_type: String },
+    TagAdded(TagInfo),
+    TagRemoved(TagInfo),
+    TagUpdated(TagInfo),
+    SymbolAdded(SymbolInfo),
+    SymbolRemoved(SymbolInfo),
+    SymbolUpdated(SymbolInfo),
+    StringFound(StringInfo),
+    StringRemoved(StringInfo),
+    TypeDefined { name: String },
+    TypeUndefined { name: String },
+    TypeReferenceChanged { name: String },
+    TypeFieldReferenceChanged { name: String, offset: u64 },
+    SegmentAdded(SegmentInfo),
+    SegmentRemoved(SegmentInfo),
+    SegmentUpdated(SegmentInfo),
+    SectionAdded(SectionInfo),
+    SectionRemoved(SectionInfo),
+    SectionUpdated(SectionInfo),
+    ComponentNameUpdated,
+    ComponentAdded,
+    ComponentMoved,
+    ComponentRemoved,
+    ComponentFunctionAdded(FunctionInfo),
+    ComponentFunctionRemoved(FunctionInfo),
+    ComponentDataVariableAdded { address: u64 },
+    ComponentDataVariableRemoved { address: u64 },
+    ExternalLibraryAdded,
+    ExternalLibraryUpdated,
+    ExternalLibraryRemoved,
+    ExternalLocationAdded,
+    ExternalLocationUpdated,
+    ExternalLocationRemoved,
+    TypeArchiveAttached,
+    TypeArchiveDetached,
+    TypeArchiveConnected,
+    TypeArchiveDisconnected,
+    UndoEntryAdded,
+    UndoEntryTaken,
+    RedoEntryTaken,
+    Rebased,
+}
+
+impl BinJuiceEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Self::StartBinaryNinja => EventKind::StartBinaryNinja,
+            Self::EndBinaryNinja => EventKind::EndBinaryNinja,
+            Self::StartBinaryView => EventKind::StartBinaryView,
+            Self::EndBinaryView => EventKind::EndBinaryView,
+            Self::NotificationBarrier => EventKind::NotificationBarrier,
+            Self::DataWritten { .. } => EventKind::DataWritten,
+            Self::DataInserted { .. } => EventKind::DataInserted,
+            Self::DataRemoved { .. } => EventKind::DataRemoved,
+            Self::FunctionAdded(_) => EventKind::FunctionAdded,
+            Self::FunctionRemoved(_) => EventKind::FunctionRemoved,
+            Self::FunctionUpdated(_) => EventKind::FunctionUpdated,
+            Self::FunctionUpdateRequested(_) => {
+                EventKind::FunctionUpdateRequested
+            }
+            Self::DataVariableAdded { .. } => EventKind::DataVariableAdded,
+            Self::DataVariableRemoved { .. } => EventKind::DataVariableRemoved,
+            Self::DataVariableUpdated { .. } => EventKind::DataVariableUpdated,
+            Self::DataMetadataUpdated { .. } => EventKind::DataMetadataUpdated,
+            Self::TagTypeUpdated { .. } => EventKind::TagTypeUpdated,
+            Self::TagAdded(_) => EventKind::TagAdded,
+            Self::TagRemoved(_) => EventKind::TagRemoved,
+            Self::TagUpdated(_) => EventKind::TagUpdated,
+            Self::SymbolAdded(_) => EventKind::SymbolAdded,
+            Self::SymbolRemoved(_) => EventKind::SymbolRemoved,
+            Self::SymbolUpdated(_) => EventKind::SymbolUpdated,
+            Self::StringFound(_) => EventKind::StringFound,
+            Self::StringRemoved(_) => EventKind::StringRemoved,
+            Self::TypeDefined { .. } => EventKind::TypeDefined,
+            Self::TypeUndefined { .. } => EventKind::TypeUndefined,
+            Self::TypeReferenceChanged { .. } => {
+                EventKind::TypeReferenceChanged
+            }
+            Self::TypeFieldReferenceChanged { .. } => {
+                EventKind::TypeFieldReferenceChanged
+            }
+            Self::SegmentAdded(_) => EventKind::SegmentAdded,
+            Self::SegmentRemoved(_) => EventKind::SegmentRemoved,
+            Self::SegmentUpdated(_) => EventKind::SegmentUpdated,
+            Self::SectionAdded(_) => EventKind::SectionAdded,
+            Self::SectionRemoved(_) => EventKind::SectionRemoved,
+            Self::SectionUpdated(_) => EventKind::SectionUpdated,
+            Self::ComponentNameUpdated => EventKind::ComponentNameUpdated,
+            Self::ComponentAdded => EventKind::ComponentAdded,
+            Self::ComponentMoved => EventKind::ComponentMoved,
+            Self::ComponentRemoved => EventKind::ComponentRemoved,
+            Self::ComponentFunctionAdded(_) => {
+                EventKind::ComponentFunctionAdded
+            }
+            Self::ComponentFunctionRemoved(_) => {
+                EventKind::ComponentFunctionRemoved
+            }
+            Self::ComponentDataVariableAdded { .. } => {
+                EventKind::ComponentDataVariableAdded
+            }
+            Self::ComponentDataVariableRemoved { .. } => {
+                EventKind::ComponentDataVariableRemoved
+            }
+            Self::ExternalLibraryAdded => EventKind::ExternalLibraryAdded,
+            Self::ExternalLibraryUpdated => EventKind::ExternalLibraryUpdated,
+            Self::ExternalLibraryRemoved => EventKind::ExternalLibraryRemoved,
+            Self::ExternalLocationAdded => EventKind::ExternalLocationAdded,
+            Self::ExternalLocationUpdated => EventKind::ExternalLocationUpdated,
+            Self::ExternalLocationRemoved => EventKind::ExternalLocationRemoved,
+            Self::TypeArchiveAttached => EventKind::TypeArchiveAttached,
+            Self::TypeArchiveDetached => EventKind::TypeArchiveDetached,
+            Self::TypeArchiveConnected => EventKind::TypeArchiveConnected,
+            Self::TypeArchiveDisconnected => EventKind::TypeArchiveDisconnected,
+            Self::UndoEntryAdded => EventKind::UndoEntryAdded,
+            Self::UndoEntryTaken => EventKind::UndoEntryTaken,
+            Self::RedoEntryTaken => EventKind::RedoEntryTaken,
+            Self::Rebased => EventKind::Rebased,
+        }
+    }
+
+    /// The args used by the filters, mappings and traces
+    pub fn args(&self) -> EventArgs {
+        match self {
+            Self::DataWritten { address, len }
+            | Self::DataInserted { address, len }
+            | Self::DataRemoved { address, len } => EventArgs {
+                address: Some(*address),
+                len: Some(*len),
+                ..EventArgs::default()
+            },
+            Self::FunctionAdded(function)
+            | Self::FunctionRemoved(function)
+            | Self::FunctionUpdated(function)
+            | Self::FunctionUpdateRequested(function)
+            | Self::ComponentFunctionAdded(function)
+            | Self::ComponentFunctionRemoved(function) => EventArgs {
+                address: Some(function.address),
+                name: Some(function.name.clone()),
+                function_size: Some(function.size),
+                basic_blocks: Some(function.basic_blocks),
+                ..EventArgs::default()
+            },
+            Self::DataVariableAdded { address }
+            | Self::DataVariableRemoved { address }
+            | Self::DataVariableUpdated { address }
+            | Self::DataMetadataUpdated { address }
+            | Self::ComponentDataVariableAdded { address }
+            | Self::ComponentDataVariableRemoved { address } => EventArgs {
+                address: Some(*address),
+                ..EventArgs::default()
+            },
+            Self::TagTypeUpdated { tag_type } => EventArgs {
+                tag_type: Some(tag_type.clone()),
+                ..EventArgs::default()
+            },
+            Self::TagAdded(tag)
+            | Self::TagRemoved(tag)
+            | Self::TagUpdated(tag) => EventArgs {
+                address: Some(tag.address),
+                tag_type: Some(tag.tag_type.clone()),
+                ..EventArgs::default()
+            },
+            Self::SymbolAdded(symbol)
+            | Self::SymbolRemoved(symbol)
+            | Self::SymbolUpdated(symbol) => EventArgs {
+                address: Some(symbol.address),
+                name: Some(symbol.name.clone()),
+                ..EventArgs::default()
+            },
+            Self::StringFound(string) | Self::StringRemoved(string) => {
+                EventArgs {
+                    address: Some(string.address),
+                    len: Some(string.len),
+                    string_type: Some(string.string_type),
+                    ..EventArgs::default()
+                }
+            }
+            Self::TypeDefined { name }
+            | Self::TypeUndefined { name }
+            | Self::TypeReferenceChanged { name } => EventArgs {
+                name: Some(name.clone()),
+                ..EventArgs::default()
+            },
+            Self::TypeFieldReferenceChanged { name, .. } => EventArgs {
+                name: Some(name.clone()),
+                ..EventArgs::default()
+            },
+            Self::SegmentAdded(segment)
+            | Self::SegmentRemoved(segment)
+            | Self::SegmentUpdated(segment) => EventArgs {
+                address: Some(segment.range.start),
+                len: Some(segment.range.end - segment.range.start),
+                permissions: Some(segment.permissions),
+                ..EventArgs::default()
+            },
+            Self::SectionAdded(section)
+            | Self::SectionRemoved(section)
+            | Self::SectionUpdated(section) => EventArgs {
+                address: Some(section.range.start),
+                len: Some(section.range.end - section.range.start),
+                section: Some(section.name.clone()),
+                ..EventArgs::default()
+            },
+            Self::StartBinaryNinja
+            | Self::EndBinaryNinja
+            | Self::StartBinaryView
+            | Self::EndBinaryView
+            | Self::NotificationBarrier
+            | Self::ComponentNameUpdated
+            | Self::ComponentAdded
+            | Self::ComponentMoved
+            | Self::ComponentRemoved
+            | Self::ExternalLibraryAdded
+            | Self::ExternalLibraryUpdated
+            | Self::ExternalLibraryRemoved
+            | Self::ExternalLocationAdded
+            | Self::ExternalLocationUpdated
+            | Self::ExternalLocationRemoved
+            | Self::TypeArchiveAttached
+            | Self::TypeArchiveDetached
+            | Self::TypeArchiveConnected
+            | Self::TypeArchiveDisconnected
+            | Self::UndoEntryAdded
+            | Self::UndoEntryTaken
+            | Self::RedoEntryTaken
+            | Self::Rebased => EventArgs::default(),
+        }
+    }
+}
+
+/// An event as received by the sinks, only decoded if a sink needs the args
+pub struct Event {
+    kind: EventKind,
+    decoded: Option<(BinJuiceEvent, Option<Arc<dyn ViewInfo>>)>,
+    args: OnceLock<EventArgs>,
+}
+
+impl Event {
+    /// The view is None for the events sent by BinJuice itself
+    pub fn new(event: BinJuiceEvent, view: Option<Arc<dyn ViewInfo>>) -> Self {
+        Self {
+            kind: event.kind(),
+            decoded: Some((event, view)),
+            args: OnceLock::new(),
+        }
+    }
+
+    /// For the sinks that only need the kind, the args are empty
+    pub fn without_args(kind: EventKind) -> Self {
+        Self {
+            kind,
+            decoded: None,
+            args: OnceLock::new(),
+        }
+    }
+
+    /// Same as new, with the args already decoded, eg: from a trace
+    pub fn with_args(kind: EventKind, args: EventArgs) -> Self {
+        Self {
+            kind,
+            decoded: None,
+            args: OnceLock::from(args),
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// None if no sink needs the args
+    pub fn decoded(&self) -> Option<&BinJuiceEvent> {
+        self.decoded.as_ref().map(|(event, _view)| event)
+    }
+
+    /// Built on the first use, so they are shared by all the sinks
+    pub fn args(&self) -> &EventArgs {
+        self.args.get_or_init(|| match &self.decoded {
+            Some((event, view)) => EventArgs {
+                view: view.clone(),
+                ..event.args()
+            },
+            None => EventArgs::default(),
+        })
+    }
+}
+
+/// The information about the view that sent the event, that is not part of
+/// the event arguments
+pub trait ViewInfo: Send + Sync {
+    /// the names of the sections containing the address
+    fn sections_at(&self, address: u64) -> Vec<String>;
+    /// the permissions of the segment containing the address
+    fn permissions_at(&self, address: u64) -> Option<u8>;
+    fn address_range(&self) -> Range<u64>;
+}
+
+/// The event arguments as optional named fields, for the filters, the
+/// mappings, the scripts and the traces
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EventArgs {
+    /// replaced by the traced view info when replaying a trace
+    #[serde(skip)]
+    pub view: Option<Arc<dyn ViewInfo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub len: Option<u64>,
+    /// symbol, function or type name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub section: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub string_type: Option<StringKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub basic_blocks: Option<u64>,
+    /// segment permissions, read = 4, write = 2 and execute = 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_serde() {
+        for &event in EventKind::ALL {
+            let yaml = serde_yaml::to_string(&event).unwrap();
+            assert_eq!(yaml.trim(), event.name());
+            assert_eq!(EventKind::from_name(event.name()), Some(event));
+        }
+        assert_eq!(EventKind::from_name("function_addded"), None);
+    }
+
+    #[test]
+    fn args_without_view_roundtrip() {
+        let args = EventArgs {
+            address: Some(0x401000),
+            string_type: Some(StringKind::Utf16),
+            ..EventArgs::default()
+        };
+        let json = serde_json::to_string(&args).unwrap();
+        assert_eq!(json, r#"{"address":4198400,"string_type":"utf16"}"#);
+        let args: EventArgs = serde_json::from_str(&json).unwrap();
+        assert_eq!(args.address, Some(0x401000));
+        assert!(args.string_type == Some(StringKind::Utf16));
+        assert!(args.len.is_none() && args.view.is_none());
+    }
+
+    #[test]
+    fn args_of_the_typed_events() {
+        let event = BinJuiceEvent::DataWritten {
+            address: 0x10,
+            len: 4,
+        };
+        assert_eq!(event.kind(), EventKind::DataWritten);
+        let args = event.args();
+        assert_eq!((args.address, args.len), (Some(0x10), Some(4)));
+        let segment = BinJuiceEvent::SegmentAdded(SegmentInfo {
+            range: 0x1000..0x3000,
+            permissions: 5,
+        });
+        let args = Event::new(segment, None).args().clone();
+        assert_eq!(args.address, Some(0x1000));
+        assert_eq!(args.len, Some(0x2000));
+        assert_eq!(args.permissions, Some(5));
+        // the offset inside the type is not an address
+        let event = BinJuiceEvent::TypeFieldReferenceChanged {
+            name: "foo".to_string(),
+            offset: 8,
+        };
+        assert!(event.args().address.is_none());
+        let event = Event::without_args(EventKind::FunctionAdded);
+        assert!(event.decoded().is_none() && event.args().name.is_none());
+    }
+}
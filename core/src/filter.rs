@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use anyhow::{Context, Result};
+
+use regex::Regex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::EventArgs;
+
+/// Only play the event if the arguments match all the configured criteria
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FilterConfig {
+    /// regex matched against the symbol, function or type name
+    pub name: Option<String>,
+    /// only the events with an address inside one of the ranges
+    pub address: Option<Vec<AddressRange>>,
+    /// only the events with an address inside one of the sections
+    pub section: Option<Vec<String>>,
+    pub string_type: Option<Vec<StringKind>>,
+    pub tag_type: Option<Vec<String>>,
+}
+
+/// Address range, the end is not included
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AddressRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StringKind {
+    Ascii,
+    Utf8,
+    Utf16,
+    Utf32,
+}
+
+pub struct EventFilter {
+    name: Option<Regex>,
+    address: Option<Vec<Range<u64>>>,
+    section: Option<Vec<String>>,
+    string_type: Option<Vec<StringKind>>,
+    tag_type: Option<Vec<String>>,
+}
+
+impl EventFilter {
+    pub fn new(config: &FilterConfig) -> Result<Self> {
+        let name = config
+            .name
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .context("Invalid name filter")?;
+        let address = config.address.as_ref().map(|ranges| {
+            ranges.iter().map(|range| range.start..range.end).collect()
+        });
+        Ok(Self {
+            name,
+            address,
+            section: config.section.clone(),
+            string_type: config.string_type.clone(),
+            tag_type: config.tag_type.clone(),
+        })
+    }
+
+    /// Events without the filtered argument never match
+    pub fn matches(&self, args: &EventArgs) -> bool {
+        if let Some(regex) = &self.name
+            && !args
+                .name
+                .as_deref()
+                .is_some_and(|name| regex.is_match(name))
+        {
+            return false;
+        }
+        if let Some(ranges) = &self.address
+            && !args.address.is_some_and(|address| {
+                ranges.iter().any(|range| range.contains(&address))
+            })
+        {
+            return false;
+        }
+        if let Some(sections) = &self.section
+            && !self.in_sections(args, sections)
+        {
+            return false;
+        }
+        if let Some(string_types) = &self.string_type
+            && !args
+                .string_type
+                .is_some_and(|string_type| string_types.contains(&string_type))
+        {
+            return false;
+        }
+        if let Some(tag_types) = &self.tag_type
+            && !args
+                .tag_type
+                .as_ref()
+                .is_some_and(|tag_type| tag_types.iter().any(|x| x == tag_type))
+        {
+            return false;
+        }
+        true
+    }
+
+    fn in_sections(&self, args: &EventArgs, sections: &[String]) -> bool {
+        // section events already carry the section name
+        if let Some(section) = &args.section {
+            return sections.contains(section);
+        }
+        let (Some(view), Some(address)) = (&args.view, args.address) else {
+            return false;
+        };
+        view.sections_at(address)
+            .iter()
+            .any(|section| sections.contains(section))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    use crate::event::ViewInfo;
+
+    struct View;
+
+    impl ViewInfo for View {
+        fn sections_at(&self, address: u64) -> Vec<String> {
+            match address {
+                0x1000..0x2000 => vec![".text".to_string()],
+                _ => vec![],
+            }
+        }
+
+        fn permissions_at(&self, _address: u64) -> Option<u8> {
+            None
+        }
+
+        fn address_range(&self) -> std::ops::Range<u64> {
+            0..0x3000
+        }
+    }
+
+    fn filter(yaml: &str) -> EventFilter {
+        EventFilter::new(&serde_yaml::from_str(yaml).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn name_regex() {
+        let filter = filter("name: ^mem");
+        let args = |name: Option<&str>| EventArgs {
+            name: name.map(str::to_string),
+            ..EventArgs::default()
+        };
+        assert!(filter.matches(&args(Some("memcpy"))));
+        assert!(!filter.matches(&args(Some("strcpy"))));
+        assert!(!filter.matches(&args(None)));
+    }
+
+    #[test]
+    fn address_ranges_exclude_the_end() {
+        let filter = filter("address: [{start: 0x10, end: 0x20}]");
+        let args = |address| EventArgs {
+            address: Some(address),
+            ..EventArgs::default()
+        };
+        assert!(filter.matches(&args(0x10)));
+        assert!(!filter.matches(&args(0x20)));
+    }
+
+    #[test]
+    fn sections_from_the_event_or_the_view() {
+        let filter = filter("section: [.text]");
+        let section = EventArgs {
+            section: Some(".text".to_string()),
+            ..EventArgs::default()
+        };
+        assert!(filter.matches(&section));
+        let args = |address| EventArgs {
+            view: Some(Arc::new(View)),
+            address: Some(address),
+            ..EventArgs::default()
+        };
+        assert!(filter.matches(&args(0x1800)));
+        assert!(!filter.matches(&args(0x2800)));
+        // without a view the section is unknown
+        let no_view = EventArgs {
+            address: Some(0x1800),
+            ..EventArgs::default()
+        };
+        assert!(!filter.matches(&no_view));
+    }
+
+    #[test]
+    fn all_criteria_must_match() {
+        let filter = filter("{string_type: [utf16], tag_type: [Bugs]}");
+        let args = |string_type, tag_type: &str| EventArgs {
+            string_type: Some(string_type),
+            tag_type: Some(tag_type.to_string()),
+            ..EventArgs::default()
+        };
+        assert!(filter.matches(&args(StringKind::Utf16, "Bugs")));
+        assert!(!filter.matches(&args(StringKind::Ascii, "Bugs")));
+        assert!(!filter.matches(&args(StringKind::Utf16, "Crashes")));
+    }
+
+    #[test]
+    fn invalid_regex() {
+        let config = serde_yaml::from_str("name: '('").unwrap();
+        assert!(EventFilter::new(&config).is_err());
+    }
+}
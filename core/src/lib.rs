@@ -0,0 +1,27 @@
+//! The BinJuice config, event mapping and audio, without Binary Ninja, so it
+//! can be tested and used by the replay tool
+
+#[macro_use]
+pub mod log;
+
+pub mod assets;
+pub mod audio;
+pub mod cache;
+pub mod config;
+pub mod event;
+pub mod filter;
+pub mod midi;
+pub mod osc;
+pub mod output;
+pub mod pack;
+pub mod playback;
+pub mod pool;
+pub mod profile;
+pub mod record;
+pub mod replay;
+pub mod scheduler;
+pub mod script;
+pub mod sink;
+pub mod sonify;
+pub mod synth;
+pub mod trace;
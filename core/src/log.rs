@@ -0,0 +1,50 @@
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error,
+}
+
+static LOGGER: OnceLock<fn(LogLevel, &str)> = OnceLock::new();
+
+/// Send the log somewhere else, eg: the Binary Ninja log, by default it goes
+/// to stderr. Only the first call has any effect.
+pub fn set_logger(logger: fn(LogLevel, &str)) {
+    let _ = LOGGER.set(logger);
+}
+
+pub fn log(level: LogLevel, msg: &str) {
+    match LOGGER.get() {
+        Some(logger) => logger(level, msg),
+        None => {
+            let level = format!("{level:?}").to_lowercase();
+            eprintln!("[{level}] {msg}");
+        }
+    }
+}
+
+macro_rules! log_dbg {
+    ($msg:expr) => {
+        #[cfg(debug_assertions)]
+        $crate::log::log($crate::log::LogLevel::Debug, $msg)
+    };
+}
+
+macro_rules! info {
+    ($msg:expr) => {
+        $crate::log::log($crate::log::LogLevel::Info, $msg)
+    };
+}
+macro_rules! warn {
+    ($msg:expr) => {
+        $crate::log::log($crate::log::LogLevel::Warning, $msg)
+    };
+}
+macro_rules! err {
+    ($msg:expr) => {
+        $crate::log::log($crate::log::LogLevel::Error, $msg)
+    };
+}
@@ -0,0 +1,560 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, EventArgs, EventKind};
+use crate::sink::{EventSink, Interest};
+use crate::sonify::ArgMapping;
+
+/// Play the events as MIDI notes
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidiConfig {
+    /// name of the virtual MIDI port created for the notes
+    pub port: Option<String>,
+    /// write the notes to this MIDI file, periodically and when Binary Ninja
+    /// exits
+    pub file: Option<PathBuf>,
+    pub notes: BTreeMap<EventKind, NoteConfig>,
+}
+
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoteConfig {
+    /// 0 to 15
+    #[serde(default)]
+    pub channel: u8,
+    pub note: MidiValue,
+    #[serde(default = "default_velocity")]
+    pub velocity: MidiValue,
+    #[serde(default = "default_duration_ms")]
+    pub duration_ms: u64,
+}
+
+fn default_velocity() -> MidiValue {
+    MidiValue::Fixed(100)
+}
+
+fn default_duration_ms() -> u64 {
+    200
+}
+
+/// A note or velocity, fixed or from the event arguments
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MidiValue {
+    Fixed(u8),
+    /// without the argument, the start of the output range is used
+    Mapped(ArgMapping),
+}
+
+impl MidiValue {
+    fn validate(&self) -> Result<()> {
+        let valid = |value: f64| (0.0..=127.0).contains(&value);
+        match self {
+            MidiValue::Fixed(value) if valid((*value).into()) => Ok(()),
+            MidiValue::Fixed(value) => bail!("Invalid MIDI value {value}"),
+            MidiValue::Mapped(mapping) => {
+                mapping.validate()?;
+                let [start, end] = mapping.output;
+                if !valid(start) || !valid(end) {
+                    bail!("Invalid MIDI output range {start}..{end}");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn value(&self, args: &EventArgs) -> u8 {
+        match self {
+            MidiValue::Fixed(value) => *value,
+            MidiValue::Mapped(mapping) => {
+                let value = mapping.map(args).unwrap_or(mapping.output[0]);
+                value.round() as u8
+            }
+        }
+    }
+}
+
+impl NoteConfig {
+    fn validate(&self) -> Result<()> {
+        if self.channel > 15 {
+            bail!("Invalid MIDI channel {}", self.channel);
+        }
+        self.note.validate().context("Invalid note")?;
+        self.velocity.validate().context("Invalid velocity")?;
+        if self.duration_ms == 0 {
+            bail!("The duration can't be zero");
+        }
+        Ok(())
+    }
+
+    fn needs_args(&self) -> bool {
+        [self.note, self.velocity]
+            .iter()
+            .any(|value| matches!(value, MidiValue::Mapped(_)))
+    }
+
+    // the note on and off messages
+    fn messages(&self, args: &EventArgs) -> ([u8; 3], [u8; 3]) {
+        let note = self.note.value(args);
+        let velocity = self.velocity.value(args);
+        (
+            [0x90 | self.channel, note, velocity],
+            [0x80 | self.channel, note, 0],
+        )
+    }
+}
+
+pub struct MidiSink {
+    notes: BTreeMap<EventKind, NoteConfig>,
+    port: Option<(String, Sender<Note>)>,
+    // shared with the sinks reopened from this one
+    file: Option<(PathBuf, Arc<Mutex<MidiFile>>)>,
+}
+
+impl MidiSink {
+    pub fn open(config: &MidiConfig) -> Result<Self> {
+        Self::reopen(config, None)
+    }
+
+    /// Open the sink for a new config, keeping the port and the notes of the
+    /// previous sink if they are still the same
+    pub fn reopen(
+        config: &MidiConfig,
+        previous: Option<&Self>,
+    ) -> Result<Self> {
+        for (event, note) in &config.notes {
+            note.validate()
+                .with_context(|| format!("Invalid MIDI note for {event}"))?;
+        }
+        let previous_port = previous
+            .and_then(|previous| previous.port.as_ref())
+            .filter(|(name, _)| Some(name) == config.port.as_ref());
+        let port = match (&config.port, previous_port) {
+            (Some(_), Some(port)) => Some(port.clone()),
+            (Some(name), None) => Some((name.clone(), open_port(name)?)),
+            (None, _) => None,
+        };
+        let previous_file = previous
+            .and_then(|previous| previous.file.as_ref())
+            .filter(|(path, _)| Some(path) == config.file.as_ref());
+        let file = match (&config.file, previous_file) {
+            (Some(_), Some(file)) => Some(file.clone()),
+            (Some(path), None) => {
+                let file = Arc::new(Mutex::new(MidiFile::new()));
+                autosave(path.clone(), Arc::downgrade(&file));
+                Some((path.clone(), file))
+            }
+            (None, _) => None,
+        };
+        Ok(Self {
+            notes: config.notes.clone(),
+            port,
+            file,
+        })
+    }
+
+    /// Write the notes played so far to the MIDI file, if any
+    pub fn save(&self) -> Result<()> {
+        let Some((path, file)) = &self.file else {
+            return Ok(());
+        };
+        let mut file = file.lock().unwrap();
+        file.saved = file.messages.len();
+        write(path, &file.encode())?;
+        info!(&format!(
+            "MIDI file written to {}, {} notes",
+            path.display(),
+            file.messages.len() / 2
+        ));
+        Ok(())
+    }
+
+    /// The MIDI file the notes are written to
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_ref().map(|(path, _)| path.as_path())
+    }
+}
+
+impl EventSink for MidiSink {
+    fn interest(&self, kind: EventKind) -> Interest {
+        match self.notes.get(&kind) {
+            None => Interest::None,
+            Some(note) if note.needs_args() => Interest::Args,
+            Some(_) => Interest::Kind,
+        }
+    }
+
+    fn handle(&self, event: &Event) {
+        let Some(config) = self.notes.get(&event.kind()) else {
+            return;
+        };
+        let (on, off) = config.messages(event.args());
+        let duration = Duration::from_millis(config.duration_ms);
+        if let Some((_name, port)) = &self.port {
+            let note = Note {
+                on,
+                off,
+                off_at: Instant::now() + duration,
+            };
+            if port.send(note).is_err() {
+                err!("The MIDI port is closed");
+            }
+        }
+        if let Some((_path, file)) = &self.file {
+            file.lock().unwrap().add(on, off, duration);
+        }
+    }
+}
+
+struct Note {
+    on: [u8; 3],
+    off: [u8; 3],
+    off_at: Instant,
+}
+
+// the connection is only used by the port thread, that also sends the note
+// off messages when they are due
+fn open_port(name: &str) -> Result<Sender<Note>> {
+    let (sender, receiver) = mpsc::channel();
+    let (result_sender, result) = mpsc::sync_channel(1);
+    let name = name.to_string();
+    std::thread::spawn(move || match connect(&name) {
+        Ok(connection) => {
+            let _ = result_sender.send(Ok(()));
+            port_thread(connection, receiver);
+        }
+        Err(e) => {
+            let _ = result_sender.send(Err(e));
+        }
+    });
+    result
+        .recv()
+        .context("The MIDI port thread stopped")?
+        .context("Unable to open the MIDI port")?;
+    Ok(sender)
+}
+
+#[cfg(unix)]
+fn connect(name: &str) -> Result<midir::MidiOutputConnection> {
+    use midir::os::unix::VirtualOutput;
+
+    let output = midir::MidiOutput::new("BinJuice")?;
+    // the error holds the output, that is not Send
+    output
+        .create_virtual(name)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+#[cfg(not(unix))]
+fn connect(_name: &str) -> Result<midir::MidiOutputConnection> {
+    bail!("Virtual MIDI ports are not supported on this system")
+}
+
+fn port_thread(
+    mut connection: midir::MidiOutputConnection,
+    receiver: Receiver<Note>,
+) {
+    let mut pending: BinaryHeap<Reverse<(Instant, [u8; 3])>> =
+        BinaryHeap::new();
+    let mut send = |message: &[u8]| {
+        if let Err(e) = connection.send(message) {
+            log_dbg!(&format!("Unable to send the MIDI message: {e}"));
+        }
+    };
+    loop {
+        let received = match pending.peek() {
+            Some(Reverse((off_at, _))) => receiver
+                .recv_timeout(off_at.saturating_duration_since(Instant::now())),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(note) => {
+                send(&note.on);
+                pending.push(Reverse((note.off_at, note.off)));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let now = Instant::now();
+        while let Some(Reverse((off_at, off))) = pending.peek().copied()
+            && off_at <= now
+        {
+            pending.pop();
+            send(&off);
+        }
+    }
+    // don't leave notes hanging
+    for Reverse((_, off)) in pending {
+        send(&off);
+    }
+}
+
+// 120 bpm, so one tick is 500000 / 480 microseconds
+const TICKS_PER_QUARTER: u16 = 480;
+const MICROS_PER_QUARTER: u32 = 500_000;
+
+// a few MB in memory, the later notes are only sent to the port
+const MAX_NOTES: usize = 200_000;
+// how often the new notes are written, in case the process is killed
+const SAVE_TIME: Duration = Duration::from_secs(30);
+
+/// The notes since the sink was opened, written as a MIDI file
+struct MidiFile {
+    start: Instant,
+    messages: Vec<(Duration, [u8; 3])>,
+    // the messages already written to the file
+    saved: usize,
+}
+
+// until the file is dropped, eg: its path changed on reload
+fn autosave(path: PathBuf, file: Weak<Mutex<MidiFile>>) {
+    let spawned = std::thread::Builder::new()
+        .name("BinJuice MIDI autosave".to_string())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(SAVE_TIME);
+                let Some(file) = file.upgrade() else {
+                    break;
+                };
+                // encoded with the lock, but written without it
+                let encoded = {
+                    let mut file = file.lock().unwrap();
+                    if file.saved == file.messages.len() {
+                        continue;
+                    }
+                    file.saved = file.messages.len();
+                    file.encode()
+                };
+                drop(file);
+                if let Err(e) = write(&path, &encoded) {
+                    err!(&format!("Unable to save the MIDI file: {e:#}"));
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        warn!(&format!("Unable to spawn the MIDI autosave thread: {e}"));
+    }
+}
+
+fn write(path: &Path, encoded: &[u8]) -> Result<()> {
+    std::fs::write(path, encoded)
+        .with_context(|| format!("Unable to write {}", path.display()))
+}
+
+impl MidiFile {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            messages: vec![],
+            saved: 0,
+        }
+    }
+
+    fn add(&mut self, on: [u8; 3], off: [u8; 3], duration: Duration) {
+        if self.messages.len() >= MAX_NOTES * 2 {
+            return;
+        }
+        if self.messages.len() == (MAX_NOTES - 1) * 2 {
+            warn!(&format!(
+                "The MIDI file is full, only the first {MAX_NOTES} notes \
+                are written"
+            ));
+        }
+        let offset = self.start.elapsed();
+        self.messages.push((offset, on));
+        self.messages.push((offset + duration, off));
+    }
+
+    // a format 0 file, with a single track
+    fn encode(&self) -> Vec<u8> {
+        let mut messages = self.messages.clone();
+        // the note off first, so a repeated note is not cut short
+        messages.sort_by_key(|(offset, message)| (*offset, message[0] >= 0x90));
+        let mut track = vec![0x00, 0xFF, 0x51, 0x03];
+        track.extend(&MICROS_PER_QUARTER.to_be_bytes()[1..]);
+        let mut last_tick = 0;
+        for (offset, message) in messages {
+            let tick = ticks(offset);
+            push_varlen(&mut track, tick - last_tick);
+            track.extend(message);
+            last_tick = tick;
+        }
+        track.extend([0x00, 0xFF, 0x2F, 0x00]);
+
+        let mut file = b"MThd".to_vec();
+        file.extend(6u32.to_be_bytes());
+        // format 0, one track
+        file.extend(0u16.to_be_bytes());
+        file.extend(1u16.to_be_bytes());
+        file.extend(TICKS_PER_QUARTER.to_be_bytes());
+        file.extend(b"MTrk");
+        file.extend((track.len() as u32).to_be_bytes());
+        file.extend(track);
+        file
+    }
+}
+
+fn ticks(offset: Duration) -> u32 {
+    let ticks = offset.as_micros() * u128::from(TICKS_PER_QUARTER)
+        / u128::from(MICROS_PER_QUARTER);
+    // the largest variable length quantity
+    ticks.min(0x0FFF_FFFF) as u32
+}
+
+fn push_varlen(buf: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(yaml: &str) -> NoteConfig {
+        let note: NoteConfig = serde_yaml::from_str(yaml).unwrap();
+        note.validate().unwrap();
+        note
+    }
+
+    fn len(len: u64) -> EventArgs {
+        EventArgs {
+            len: Some(len),
+            ..EventArgs::default()
+        }
+    }
+
+    #[test]
+    fn fixed_and_mapped_values() {
+        let note = note(
+            "{channel: 2, note: 60, \
+            velocity: {from: len, input: [0, 10], output: [20, 120]}}",
+        );
+        assert!(note.needs_args());
+        assert_eq!(note.messages(&len(5)), ([0x92, 60, 70], [0x82, 60, 0]));
+        // without the argument, the start of the range
+        let (on, _off) = note.messages(&EventArgs::default());
+        assert_eq!(on, [0x92, 60, 20]);
+    }
+
+    #[test]
+    fn invalid_notes() {
+        let invalid = |yaml| {
+            let note: NoteConfig = serde_yaml::from_str(yaml).unwrap();
+            note.validate().is_err()
+        };
+        assert!(invalid("{channel: 16, note: 60}"));
+        assert!(invalid("{note: 128}"));
+        assert!(invalid("{note: 60, duration_ms: 0}"));
+        assert!(invalid(
+            "{note: {from: len, input: [0, 1], output: [0, 200]}}"
+        ));
+        assert!(invalid("{note: {from: len, output: [0, 127]}}"));
+    }
+
+    #[test]
+    fn varlen() {
+        let encode = |value| {
+            let mut buf = vec![];
+            push_varlen(&mut buf, value);
+            buf
+        };
+        assert_eq!(encode(0), [0x00]);
+        assert_eq!(encode(0x7F), [0x7F]);
+        assert_eq!(encode(0x80), [0x81, 0x00]);
+        assert_eq!(encode(0x0FFF_FFFF), [0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn encode_file() {
+        let mut file = MidiFile::new();
+        let at = |ms| Duration::from_millis(ms);
+        // one quarter note, then the same note again right after
+        file.messages = vec![
+            (at(0), [0x90, 60, 100]),
+            (at(500), [0x80, 60, 0]),
+            (at(500), [0x90, 60, 100]),
+            (at(1000), [0x80, 60, 0]),
+        ];
+        let encoded = file.encode();
+        assert_eq!(&encoded[..8], b"MThd\0\0\0\x06");
+        assert_eq!(&encoded[8..14], [0, 0, 0, 1, 0x01, 0xE0]);
+        assert_eq!(&encoded[14..18], b"MTrk");
+        let track = &encoded[22..];
+        assert_eq!(encoded[18..22], (track.len() as u32).to_be_bytes());
+        assert_eq!(
+            track,
+            [
+                0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, //
+                0x00, 0x90, 60, 100, //
+                0x83, 0x60, 0x80, 60, 0, //
+                0x00, 0x90, 60, 100, //
+                0x83, 0x60, 0x80, 60, 0, //
+                0x00, 0xFF, 0x2F, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn sink_records_to_file() {
+        let config: MidiConfig = serde_yaml::from_str(
+            "{file: /dev/null, notes: {function_added: {note: 60}}}",
+        )
+        .unwrap();
+        let sink = MidiSink::open(&config).unwrap();
+        assert_eq!(sink.interest(EventKind::FunctionAdded), Interest::Kind);
+        assert_eq!(sink.interest(EventKind::TagAdded), Interest::None);
+        let event =
+            Event::without_args(EventKind::FunctionAdded);
+        sink.handle(&event);
+        let (_path, file) = sink.file.as_ref().unwrap();
+        let messages = &file.lock().unwrap().messages;
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].0 - messages[0].0, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn reopen_keeps_the_notes() {
+        let config = |file| -> MidiConfig {
+            serde_yaml::from_str(&format!(
+                "{{file: {file}, notes: {{function_added: {{note: 60}}}}}}"
+            ))
+            .unwrap()
+        };
+        let sink = MidiSink::open(&config("/dev/null")).unwrap();
+        let event =
+            Event::without_args(EventKind::FunctionAdded);
+        sink.handle(&event);
+        let notes = |sink: &MidiSink| {
+            let (_path, file) = sink.file.as_ref().unwrap();
+            file.lock().unwrap().messages.len() / 2
+        };
+        let same = MidiSink::reopen(&config("/dev/null"), Some(&sink)).unwrap();
+        assert_eq!(notes(&same), 1);
+        let other =
+            MidiSink::reopen(&config("/dev/zero"), Some(&sink)).unwrap();
+        assert_eq!(notes(&other), 0);
+    }
+
+    #[test]
+    fn file_is_capped() {
+        let mut file = MidiFile::new();
+        for _ in 0..MAX_NOTES + 10 {
+            file.add([0x90, 60, 100], [0x80, 60, 0], Duration::ZERO);
+        }
+        assert_eq!(file.messages.len(), MAX_NOTES * 2);
+    }
+}
@@ -0,0 +1,187 @@
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use anyhow::{Context, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, EventArgs, EventKind};
+use crate::sink::{EventSink, Interest};
+
+/// Send the events as OSC messages over UDP, eg: to SuperCollider
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OscConfig {
+    pub host: String,
+    pub port: u16,
+    /// the message address is the prefix and the event name
+    pub prefix: String,
+    /// only send the events matching one of the patterns, all if empty
+    pub events: Vec<String>,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            // the sclang default port
+            port: 57120,
+            prefix: "/binjuice".to_string(),
+            events: vec![],
+        }
+    }
+}
+
+pub struct OscSink {
+    socket: UdpSocket,
+    target: SocketAddr,
+    prefix: String,
+    events: Vec<glob::Pattern>,
+}
+
+impl OscSink {
+    pub fn open(config: &OscConfig) -> Result<Self> {
+        let target = (config.host.as_str(), config.port)
+            .to_socket_addrs()
+            .and_then(|mut addrs| {
+                addrs.next().ok_or(std::io::ErrorKind::NotFound.into())
+            })
+            .with_context(|| {
+                format!("Unable to resolve the OSC host {}", config.host)
+            })?;
+        let local: SocketAddr = if target.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket =
+            UdpSocket::bind(local).context("Unable to open the OSC socket")?;
+        let events = config
+            .events
+            .iter()
+            .map(|pattern| {
+                glob::Pattern::new(pattern).with_context(|| {
+                    format!("Invalid OSC event pattern {pattern}")
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            socket,
+            target,
+            prefix: config.prefix.trim_end_matches('/').to_string(),
+            events,
+        })
+    }
+}
+
+impl EventSink for OscSink {
+    fn interest(&self, kind: EventKind) -> Interest {
+        let sent = self.events.is_empty()
+            || self
+                .events
+                .iter()
+                .any(|pattern| pattern.matches(kind.name()));
+        if sent { Interest::Args } else { Interest::None }
+    }
+
+    fn handle(&self, event: &Event) {
+        let message = message(&self.prefix, event.kind(), event.args());
+        // nobody listening is not an error, the engine may start later
+        if let Err(e) = self.socket.send_to(&message, self.target) {
+            log_dbg!(&format!("Unable to send the OSC message: {e}"));
+        }
+    }
+}
+
+/// Encode the event as an OSC message, the args are sent as name and value
+/// pairs, the numbers as int64 and the rest as strings
+fn message(prefix: &str, kind: EventKind, args: &EventArgs) -> Vec<u8> {
+    let mut tags = String::from(",");
+    let mut data = vec![];
+    // only plain data, so it can't fail
+    let serde_json::Value::Object(args) = serde_json::to_value(args).unwrap()
+    else {
+        unreachable!("the args are a struct");
+    };
+    for (name, value) in args {
+        tags.push('s');
+        push_str(&mut data, &name);
+        match value {
+            serde_json::Value::Number(number) => {
+                tags.push('h');
+                let number = number.as_u64().unwrap_or_default();
+                data.extend_from_slice(&number.to_be_bytes());
+            }
+            serde_json::Value::String(value) => {
+                tags.push('s');
+                push_str(&mut data, &value);
+            }
+            value => {
+                tags.push('s');
+                push_str(&mut data, &value.to_string());
+            }
+        }
+    }
+    let mut message = vec![];
+    push_str(&mut message, &format!("{prefix}/{}", kind.name()));
+    push_str(&mut message, &tags);
+    message.extend(data);
+    message
+}
+
+// the OSC strings are null terminated and padded to 4 bytes
+fn push_str(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    let padding = 4 - value.len() % 4;
+    buf.extend(std::iter::repeat_n(0, padding));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::filter::StringKind;
+
+    #[test]
+    fn encode_message() {
+        let args = EventArgs {
+            address: Some(0x401000),
+            string_type: Some(StringKind::Ascii),
+            ..EventArgs::default()
+        };
+        let message = message("/bj", EventKind::StringFound, &args);
+        let mut expected = b"/bj/string_found\0\0\0\0,shss\0\0\0".to_vec();
+        expected.extend(b"address\0");
+        expected.extend(0x401000u64.to_be_bytes());
+        expected.extend(b"string_type\0ascii\0\0\0");
+        assert_eq!(message, expected);
+    }
+
+    #[test]
+    fn send_to_socket() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let config = OscConfig {
+            host: "127.0.0.1".to_string(),
+            port: receiver.local_addr().unwrap().port(),
+            events: vec!["function_*".to_string()],
+            ..OscConfig::default()
+        };
+        let sink = OscSink::open(&config).unwrap();
+        assert_eq!(sink.interest(EventKind::TagAdded), Interest::None);
+        assert_eq!(sink.interest(EventKind::FunctionAdded), Interest::Args);
+        let event =
+            Event::without_args(EventKind::FunctionAdded);
+        sink.handle(&event);
+        let mut buf = [0; 64];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"/binjuice/function_added\0\0\0\0,\0\0\0");
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let config = OscConfig {
+            events: vec!["[".to_string()],
+            ..OscConfig::default()
+        };
+        assert!(OscSink::open(&config).is_err());
+    }
+}
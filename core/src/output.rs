@@ -0,0 +1,451 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{Context, Result};
+
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::cpal::{self, Device};
+use rodio::{OutputStream, OutputStreamBuilder, Source};
+
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{AudioSink, PendingSound};
+use crate::record::Recorder;
+use crate::scheduler::Voice;
+
+/// What to do when there is no audio device, eg: headless on a server
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FallbackConfig {
+    /// write the events to the Binary Ninja log
+    pub log: bool,
+    /// append the events to this file
+    pub file: Option<PathBuf>,
+    /// mix the sounds into this WAV file, written while they play and
+    /// completed when Binary Ninja exits
+    pub record: Option<PathBuf>,
+}
+
+impl FallbackConfig {
+    fn is_enabled(&self) -> bool {
+        self.log || self.file.is_some() || self.record.is_some()
+    }
+}
+
+// how often the devices are checked for changes
+const DEVICE_CHECK_TIME: Duration = Duration::from_secs(2);
+// how often a selected device that is not available is searched for
+const DEVICE_SEARCH_TIME: Duration = Duration::from_secs(30);
+// how often drain checks if the sounds ended
+const DRAIN_CHECK_TIME: Duration = Duration::from_millis(10);
+
+/// The names of the audio output devices
+pub fn device_names() -> Result<Vec<String>> {
+    let devices = cpal::default_host()
+        .output_devices()
+        .context("Unable to list the audio devices")?;
+    Ok(devices.filter_map(|device| device.name().ok()).collect())
+}
+
+/// Where the sounds end up
+pub struct Output {
+    sink: OutputSink,
+    // while recording, the sounds are also mixed into it
+    recorder: Mutex<Option<Recorder>>,
+}
+
+enum OutputSink {
+    Stream(StreamOutput),
+    Fallback(FallbackOutput),
+}
+
+impl Output {
+    pub fn open(
+        device: Option<String>,
+        fallback: &FallbackConfig,
+    ) -> Result<Self> {
+        log_dbg!("Getting output stream");
+        let (sink, record_path) = match StreamOutput::open(device) {
+            Ok(stream) => (OutputSink::Stream(stream), None),
+            Err(e) if fallback.is_enabled() => {
+                warn!(&format!(
+                    "Unable to open the audio device, using the fallback: {e}"
+                ));
+                let fallback_output = FallbackOutput::new(fallback)?;
+                (
+                    OutputSink::Fallback(fallback_output),
+                    fallback.record.clone(),
+                )
+            }
+            Err(e) => return Err(e),
+        };
+        let recorder =
+            record_path.as_deref().map(Recorder::create).transpose()?;
+        Ok(Self {
+            sink,
+            recorder: Mutex::new(recorder),
+        })
+    }
+
+    /// Select the device, it's opened in the background
+    pub fn set_device(&self, device: Option<String>) {
+        if let OutputSink::Stream(stream) = &self.sink {
+            *stream.device.write().unwrap() = device;
+        }
+    }
+
+    /// Reopen the stream when the device is lost or the selected device
+    /// changes, eg: the headphones are plugged in
+    pub fn monitor(&'static self) -> Result<()> {
+        let OutputSink::Stream(stream) = &self.sink else {
+            return Ok(());
+        };
+        std::thread::Builder::new()
+            .name("BinJuice device monitor".to_string())
+            .spawn(|| {
+                loop {
+                    std::thread::sleep(DEVICE_CHECK_TIME);
+                    stream.check_device();
+                }
+            })
+            .context("Unable to spawn the device monitor thread")?;
+        Ok(())
+    }
+
+    /// Wait until the sounds playing end, at most `timeout`, eg: for the
+    /// end_binary_ninja sound before exiting
+    pub fn drain(&self, timeout: Duration) {
+        let OutputSink::Stream(stream) = &self.sink else {
+            return;
+        };
+        let deadline = Instant::now() + timeout;
+        while stream.playing.load(Ordering::Relaxed) > 0
+            && Instant::now() < deadline
+        {
+            std::thread::sleep(DRAIN_CHECK_TIME);
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.lock().unwrap().is_some()
+    }
+
+    /// Mix all the sounds played from now on, until stop_recording
+    pub fn start_recording(&self) -> Result<()> {
+        let mut recorder = self.recorder.lock().unwrap();
+        if recorder.is_none() {
+            *recorder = Some(Recorder::temporary()?);
+        }
+        Ok(())
+    }
+
+    pub fn stop_recording(&self) -> Option<Recorder> {
+        self.recorder.lock().unwrap().take()
+    }
+
+    /// Write the recording made without an audio device so far, it continues
+    /// for the files opened later
+    pub fn save_recording(&self) -> Result<()> {
+        match &*self.recorder.lock().unwrap() {
+            Some(recorder) => recorder.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// The fallback doesn't need the audio, so it's only decoded if necessary
+    pub fn play_source<S>(&self, name: &str, source: impl FnOnce() -> Option<S>)
+    where
+        S: Source + Send + 'static,
+    {
+        let recorder = self.recorder.lock().unwrap();
+        let Some(recorder) = recorder.as_ref() else {
+            self.sink.play(name, source);
+            return;
+        };
+        // while recording, the samples are copied to the recorder as they
+        // play, so the same samples are recorded and played
+        let Some(source) = source() else {
+            return;
+        };
+        match &self.sink {
+            OutputSink::Stream(stream) => {
+                stream.add(recorder.tee(name, source));
+            }
+            OutputSink::Fallback(fallback) => {
+                fallback.write(name);
+                recorder.add(name, source);
+            }
+        }
+    }
+}
+
+impl AudioSink for Output {
+    fn play(&self, sound: PendingSound) {
+        self.play_source(sound.event().name(), || sound.decode());
+    }
+}
+
+impl OutputSink {
+    fn play<S>(&self, name: &str, source: impl FnOnce() -> Option<S>)
+    where
+        S: Source + Send + 'static,
+    {
+        match self {
+            OutputSink::Stream(stream) => {
+                if let Some(source) = source() {
+                    stream.add(source);
+                }
+            }
+            OutputSink::Fallback(fallback) => fallback.write(name),
+        }
+    }
+}
+
+/// The audio device output, reopened in the background if it changes
+pub struct StreamOutput {
+    // the selected device name, None for the default device
+    device: RwLock<Option<String>>,
+    stream: Mutex<OpenStream>,
+    // set by the stream when the device disappears
+    lost: Arc<AtomicBool>,
+    // only log the first failure to reopen the device
+    failed: AtomicBool,
+    // when the devices were last listed
+    searched: Mutex<Instant>,
+    // the sounds added to the mixer that didn't end yet
+    playing: Arc<AtomicUsize>,
+}
+
+struct OpenStream {
+    stream: OutputStream,
+    device_name: String,
+    // the device selected when it was opened
+    selected: Option<String>,
+}
+
+impl StreamOutput {
+    fn open(device: Option<String>) -> Result<Self> {
+        let lost = Arc::new(AtomicBool::new(false));
+        let device_found = find_device(device.as_deref())?;
+        let stream = open_stream(device_found, device.clone(), &lost)?;
+        Ok(Self {
+            device: RwLock::new(device),
+            stream: Mutex::new(stream),
+            lost,
+            failed: AtomicBool::new(false),
+            searched: Mutex::new(Instant::now()),
+            playing: Arc::default(),
+        })
+    }
+
+    // counted as playing until it ends, for drain
+    fn add<S>(&self, source: S)
+    where
+        S: Source + Send + 'static,
+    {
+        let source = Voice::new(source, &self.playing);
+        self.stream.lock().unwrap().stream.mixer().add(source);
+    }
+
+    // enumerating the devices is slow, eg: ALSA probes each of them, so it's
+    // only done when the device is lost or another one is selected
+    fn is_current(&self, selected: &Option<String>) -> bool {
+        let stream = self.stream.lock().unwrap();
+        if self.lost.load(Ordering::Relaxed) || stream.selected != *selected {
+            return false;
+        }
+        match selected {
+            Some(name) if *name == stream.device_name => return true,
+            // the selected device may be available again
+            Some(_)
+                if self.searched.lock().unwrap().elapsed()
+                    >= DEVICE_SEARCH_TIME =>
+            {
+                return false;
+            }
+            _ => {}
+        }
+        // the default device is used, follow it when it changes
+        cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.name().ok())
+            .is_none_or(|name| name == stream.device_name)
+    }
+
+    fn check_device(&self) {
+        let selected = self.device.read().unwrap().clone();
+        if self.is_current(&selected) {
+            self.failed.store(false, Ordering::Relaxed);
+            return;
+        }
+        *self.searched.lock().unwrap() = Instant::now();
+        let lost = self.lost.load(Ordering::Relaxed);
+        let result = find_device(selected.as_deref()).and_then(|device| {
+            let name = device.name().unwrap_or_default();
+            {
+                let stream = self.stream.lock().unwrap();
+                if !lost
+                    && stream.device_name == name
+                    && stream.selected == selected
+                {
+                    return Ok(());
+                }
+            }
+            let stream = open_stream(device, selected, &self.lost)?;
+            info!(&format!("Using the audio device {name}"));
+            let old =
+                std::mem::replace(&mut *self.stream.lock().unwrap(), stream);
+            // the sounds still playing are dropped with the old stream, what
+            // releases their voices, so they don't hold max_voices forever
+            drop(old);
+            Ok(())
+        });
+        match result {
+            Ok(()) => self.failed.store(false, Ordering::Relaxed),
+            Err(e) => {
+                if !self.failed.swap(true, Ordering::Relaxed) {
+                    err!(&format!("Unable to open the audio device: {e:#}"));
+                }
+            }
+        }
+    }
+}
+
+// the selected device if available, otherwise the default one
+fn find_device(name: Option<&str>) -> Result<Device> {
+    let host = cpal::default_host();
+    if let Some(name) = name {
+        let device = host
+            .output_devices()
+            .context("Unable to list the audio devices")?
+            .find(|device| device.name().is_ok_and(|x| x == name));
+        if let Some(device) = device {
+            return Ok(device);
+        }
+        log_dbg!(&format!("Audio device {name} not found, using the default"));
+    }
+    host.default_output_device()
+        .context("No audio device available")
+}
+
+fn open_stream(
+    device: Device,
+    selected: Option<String>,
+    lost: &Arc<AtomicBool>,
+) -> Result<OpenStream> {
+    let device_name = device.name().unwrap_or_default();
+    let lost_callback = Arc::clone(lost);
+    let mut stream = OutputStreamBuilder::from_device(device)?
+        .with_error_callback(move |e| {
+            if matches!(e, cpal::StreamError::DeviceNotAvailable) {
+                lost_callback.store(true, Ordering::Relaxed);
+            }
+        })
+        .open_stream_or_fallback()
+        .with_context(|| format!("Unable to open {device_name}"))?;
+    // the old streams are dropped when the device changes, that is expected
+    stream.log_on_drop(false);
+    lost.store(false, Ordering::Relaxed);
+    Ok(OpenStream {
+        stream,
+        device_name,
+        selected,
+    })
+}
+
+/// Record the events as text instead of playing them
+pub struct FallbackOutput {
+    log: bool,
+    file: Option<Mutex<File>>,
+}
+
+impl FallbackOutput {
+    fn new(config: &FallbackConfig) -> Result<Self> {
+        let file = config
+            .file
+            .as_ref()
+            .map(|path| {
+                File::options()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| {
+                        format!("Unable to open {}", path.display())
+                    })
+            })
+            .transpose()?;
+        Ok(Self {
+            log: config.log,
+            file: file.map(Mutex::new),
+        })
+    }
+
+    fn write(&self, name: &str) {
+        if self.log {
+            info!(&format!("Event: {name}"));
+        }
+        if let Some(file) = &self.file {
+            let timestamp = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            let mut file = file.lock().unwrap();
+            if let Err(e) = writeln!(file, "{timestamp:.3} {name}") {
+                err!(&format!("Unable to write the fallback file: {e}"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+    use crate::scheduler::{ScheduleParams, Scheduler};
+
+    #[test]
+    fn fallback_file() {
+        let path = std::env::temp_dir()
+            .join(format!("binjuice-fallback-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = FallbackConfig {
+            file: Some(path.clone()),
+            ..FallbackConfig::default()
+        };
+        assert!(config.is_enabled());
+        let fallback = FallbackOutput::new(&config).unwrap();
+        fallback.write("tag_added");
+        fallback.write("tag_removed");
+        let written = std::fs::read_to_string(&path).unwrap();
+        let names: Vec<&str> = written
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(_timestamp, name)| name)
+            .collect();
+        assert_eq!(names, ["tag_added", "tag_removed"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replaced_mixer_releases_voices() {
+        let scheduler = Scheduler::new(ScheduleParams {
+            max_voices: Some(1),
+            ..ScheduleParams::default()
+        });
+        let (mixer, source) = rodio::mixer::mixer(1, 44100);
+        let sound = SamplesBuffer::new(1, 44100, vec![0.0; 44100]);
+        mixer.add(scheduler.track(sound));
+        assert!(!scheduler.admit(Instant::now()));
+        // what happens to the old stream when the device changes
+        drop(mixer);
+        drop(source);
+        assert!(scheduler.admit(Instant::now()));
+    }
+}
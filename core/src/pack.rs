@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result, bail};
+
+use serde::{Deserialize, Serialize};
+
+use zip::ZipArchive;
+use zip::read::root_dir_common_filter;
+
+use crate::config::AudioConfig;
+
+/// The `pack.yml` at the root of a sound pack
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackManifest {
+    pub name: String,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub description: Option<String>,
+    /// same as the config files, the paths are relative to the pack
+    #[serde(default)]
+    pub files: AudioConfig,
+}
+
+/// Load the pack by name, either the `<name>` directory or `<name>.zip`
+/// archive at the packs directory
+pub fn load(packs_dir: &Path, name: &str) -> Result<PackManifest> {
+    let dir = packs_dir.join(name);
+    let archive = packs_dir.join(format!("{name}.zip"));
+    let root = if dir.is_dir() {
+        dir
+    } else if archive.is_file() {
+        extract(packs_dir, &archive, name)?
+    } else {
+        bail!("Sound pack {name} not found at {}", packs_dir.display());
+    };
+
+    let manifest_path = root.join("pack.yml");
+    let file = File::open(&manifest_path).with_context(|| {
+        format!("Unable to open {}", manifest_path.display())
+    })?;
+    let mut manifest: PackManifest = serde_yaml::from_reader(file)
+        .with_context(|| format!("Invalid {}", manifest_path.display()))?;
+    manifest.files.resolve_paths(&root);
+    info!(&format!(
+        "Using sound pack {} by {}",
+        manifest.name,
+        manifest.author.as_deref().unwrap_or("unknown"),
+    ));
+    Ok(manifest)
+}
+
+// the archive is extracted, so the files can be streamed and the pools can use
+// glob patterns, it's only extracted again if the archive changed
+fn extract(
+    packs_dir: &Path,
+    archive_path: &Path,
+    name: &str,
+) -> Result<PathBuf> {
+    let extracted_dir = packs_dir.join(".extracted");
+    let target = extracted_dir.join(name);
+    let stamp_path = extracted_dir.join(format!("{name}.stamp"));
+    let stamp = stamp(archive_path).with_context(|| {
+        format!("Unable to read {}", archive_path.display())
+    })?;
+    if target.is_dir()
+        && std::fs::read_to_string(&stamp_path).is_ok_and(|old| old == stamp)
+    {
+        return Ok(target);
+    }
+
+    log_dbg!(&format!("Extracting {}", archive_path.display()));
+    if target.exists() {
+        std::fs::remove_dir_all(&target).with_context(|| {
+            format!("Unable to remove {}", target.display())
+        })?;
+    }
+    let file = File::open(archive_path).with_context(|| {
+        format!("Unable to open {}", archive_path.display())
+    })?;
+    ZipArchive::new(file)
+        .and_then(|mut archive| {
+            // the pack.yml can be inside a single root directory
+            archive.extract_unwrapped_root_dir(&target, root_dir_common_filter)
+        })
+        .with_context(|| {
+            format!("Unable to extract {}", archive_path.display())
+        })?;
+    std::fs::write(&stamp_path, stamp)
+        .with_context(|| format!("Unable to write {}", stamp_path.display()))?;
+    Ok(target)
+}
+
+// the size and modification time of the archive, a copy can keep the time of
+// the previous one, eg: when unpacked from another archive
+fn stamp(archive_path: &Path) -> std::io::Result<String> {
+    let metadata = archive_path.metadata()?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!("{} {}", metadata.len(), modified.as_nanos()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::playback::SoundConfig;
+
+    fn packs_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("binjuice-packs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn load_from_directory() {
+        let packs_dir = packs_dir("dir");
+        let root = packs_dir.join("retro");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(
+            root.join("pack.yml"),
+            "name: Retro\n\
+            files:\n  \
+              tag_added: sounds/tag.wav\n  \
+              tag_removed: builtin:remove.wav\n",
+        )
+        .unwrap();
+
+        let manifest = load(&packs_dir, "retro").unwrap();
+        assert_eq!(manifest.name, "Retro");
+        let paths: Vec<PathBuf> = manifest
+            .files
+            .iter()
+            .map(|(_event, config)| match config {
+                SoundConfig::Path(path) => path.clone(),
+                _ => panic!("not a path"),
+            })
+            .collect();
+        // relative to the pack, the built-in files untouched
+        assert_eq!(
+            paths,
+            [root.join("sounds/tag.wav"), "builtin:remove.wav".into()]
+        );
+        std::fs::remove_dir_all(&packs_dir).unwrap();
+    }
+
+    #[test]
+    fn stamp_changes_with_the_size() {
+        let packs_dir = packs_dir("stamp");
+        let archive = packs_dir.join("retro.zip");
+        std::fs::write(&archive, "one").unwrap();
+        let modified = archive.metadata().unwrap().modified().unwrap();
+        let before = stamp(&archive).unwrap();
+        assert_eq!(stamp(&archive).unwrap(), before);
+
+        // replaced, but with the same modification time
+        std::fs::write(&archive, "three").unwrap();
+        File::options()
+            .write(true)
+            .open(&archive)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_ne!(stamp(&archive).unwrap(), before);
+        std::fs::remove_dir_all(&packs_dir).unwrap();
+    }
+
+    #[test]
+    fn missing_pack() {
+        let packs_dir = packs_dir("missing");
+        assert!(load(&packs_dir, "retro").is_err());
+        std::fs::remove_dir_all(&packs_dir).unwrap();
+    }
+}
@@ -0,0 +1,495 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+use rodio::source::{SeekError, UniformSourceIterator};
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::cache::{ClipData, SampleCache};
+use crate::event::EventArgs;
+use crate::filter::{EventFilter, FilterConfig};
+use crate::pool::{PoolEntry, SoundPool, resolve_path};
+use crate::scheduler::{CoalesceParams, ScheduleParams, Scheduler};
+use crate::sonify::ParamMapping;
+use crate::synth::SynthConfig;
+
+/// The config of an event, either just the file path, a list of files or a
+/// table with the file(s) or synth, filter and the playback/scheduling
+/// parameters
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum SoundConfig {
+    Path(PathBuf),
+    Pool(Vec<PoolEntry>),
+    Detailed(Box<DetailedSoundConfig>),
+}
+
+// picked by the shape, so the errors inside the pool or the table are
+// reported, instead of not matching any variant
+impl<'de> Deserialize<'de> for SoundConfig {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct SoundVisitor;
+        impl<'de> Visitor<'de> for SoundVisitor {
+            type Value = SoundConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a path, a list of files or a table")
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                path: &str,
+            ) -> Result<SoundConfig, E> {
+                Ok(SoundConfig::Path(path.into()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> Result<SoundConfig, A::Error> {
+                let pool = Vec::deserialize(SeqAccessDeserializer::new(seq))?;
+                Ok(SoundConfig::Pool(pool))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<SoundConfig, A::Error> {
+                let detailed = DetailedSoundConfig::deserialize(
+                    MapAccessDeserializer::new(map),
+                )?;
+                Ok(SoundConfig::Detailed(Box::new(detailed)))
+            }
+        }
+
+        deserializer.deserialize_any(SoundVisitor)
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(from = "DetailedFields")]
+pub struct DetailedSoundConfig {
+    pub path: Option<PathBuf>,
+    pub pool: Option<Vec<PoolEntry>>,
+    pub synth: Option<SynthConfig>,
+    #[serde(default)]
+    pub no_repeat: bool,
+    pub filter: Option<FilterConfig>,
+    /// set the playback parameters from the event arguments
+    #[serde(default)]
+    pub map: Vec<ParamMapping>,
+    #[serde(flatten)]
+    pub params: PlaybackParams,
+    #[serde(flatten)]
+    pub schedule: ScheduleParams,
+}
+
+// flatten doesn't support deny_unknown_fields, so the table is read with all
+// the fields listed, to report the typos
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DetailedFields {
+    path: Option<PathBuf>,
+    pool: Option<Vec<PoolEntry>>,
+    synth: Option<SynthConfig>,
+    #[serde(default)]
+    no_repeat: bool,
+    filter: Option<FilterConfig>,
+    #[serde(default)]
+    map: Vec<ParamMapping>,
+    volume: Option<f32>,
+    speed: Option<f32>,
+    pan: Option<f32>,
+    delay_ms: Option<u64>,
+    max_duration_ms: Option<u64>,
+    low_pass_hz: Option<u32>,
+    cooldown_ms: Option<u64>,
+    max_voices: Option<usize>,
+    coalesce: Option<CoalesceParams>,
+}
+
+impl From<DetailedFields> for DetailedSoundConfig {
+    fn from(fields: DetailedFields) -> Self {
+        Self {
+            path: fields.path,
+            pool: fields.pool,
+            synth: fields.synth,
+            no_repeat: fields.no_repeat,
+            filter: fields.filter,
+            map: fields.map,
+            params: PlaybackParams {
+                volume: fields.volume,
+                speed: fields.speed,
+                pan: fields.pan,
+                delay_ms: fields.delay_ms,
+                max_duration_ms: fields.max_duration_ms,
+                low_pass_hz: fields.low_pass_hz,
+            },
+            schedule: ScheduleParams {
+                cooldown_ms: fields.cooldown_ms,
+                max_voices: fields.max_voices,
+                coalesce: fields.coalesce,
+            },
+        }
+    }
+}
+
+impl SoundConfig {
+    pub fn load_pool(&self, cache: &mut SampleCache) -> Result<SoundPool> {
+        let detailed = match self {
+            SoundConfig::Path(path) => {
+                let entry = PoolEntry::Path(path.clone());
+                return SoundPool::load(&[entry], false, cache);
+            }
+            SoundConfig::Pool(pool) => {
+                return SoundPool::load(pool, false, cache);
+            }
+            SoundConfig::Detailed(detailed) => detailed,
+        };
+        match (&detailed.path, &detailed.pool, &detailed.synth) {
+            (Some(path), None, None) => {
+                let entry = PoolEntry::Path(path.clone());
+                SoundPool::load(&[entry], false, cache)
+            }
+            (None, Some(pool), None) => {
+                SoundPool::load(pool, detailed.no_repeat, cache)
+            }
+            (None, None, Some(synth)) => {
+                synth.validate()?;
+                Ok(SoundPool::single(ClipData::Decoded(synth.render())))
+            }
+            (None, None, None) => bail!("Missing the path, pool or synth"),
+            _ => bail!("Only one of path, pool or synth can be used"),
+        }
+    }
+
+    /// Make the relative paths relative to `base`
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let (path, pool) = match self {
+            SoundConfig::Path(path) => (Some(path), None),
+            SoundConfig::Pool(pool) => (None, Some(pool)),
+            SoundConfig::Detailed(detailed) => {
+                (detailed.path.as_mut(), detailed.pool.as_mut())
+            }
+        };
+        if let Some(path) = path {
+            *path = resolve_path(base, path);
+        }
+        for entry in pool.into_iter().flatten() {
+            entry.resolve_path(base);
+        }
+    }
+
+    fn detailed(&self) -> Option<&DetailedSoundConfig> {
+        match self {
+            SoundConfig::Path(_) | SoundConfig::Pool(_) => None,
+            SoundConfig::Detailed(detailed) => Some(detailed),
+        }
+    }
+
+    pub fn params(&self) -> PlaybackParams {
+        self.detailed()
+            .map(|detailed| detailed.params)
+            .unwrap_or_default()
+    }
+
+    pub fn filter(&self) -> Option<&FilterConfig> {
+        self.detailed()?.filter.as_ref()
+    }
+
+    pub fn mappings(&self) -> &[ParamMapping] {
+        self.detailed()
+            .map(|detailed| detailed.map.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn schedule(&self) -> ScheduleParams {
+        self.detailed()
+            .map(|detailed| detailed.schedule)
+            .unwrap_or_default()
+    }
+}
+
+/// Adjustments applied to the sound before it's sent to the mixer
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct PlaybackParams {
+    /// volume multiplier, 1.0 is the original volume
+    pub volume: Option<f32>,
+    /// speed multiplier, this also changes the pitch
+    pub speed: Option<f32>,
+    /// stereo position, from -1.0 (left) to 1.0 (right)
+    pub pan: Option<f32>,
+    /// wait before start playing
+    pub delay_ms: Option<u64>,
+    /// cut the sound if longer than this
+    pub max_duration_ms: Option<u64>,
+    /// cut the frequencies above this, a lower value makes a duller sound
+    pub low_pass_hz: Option<u32>,
+}
+
+impl PlaybackParams {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(volume) = self.volume
+            && !(volume >= 0.0 && volume.is_finite())
+        {
+            bail!("Invalid volume {volume}, it can't be negative");
+        }
+        if let Some(speed) = self.speed
+            && !(speed > 0.0 && speed.is_finite())
+        {
+            bail!("Invalid speed {speed}, it needs to be bigger than zero");
+        }
+        if let Some(pan) = self.pan
+            && !(-1.0..=1.0).contains(&pan)
+        {
+            bail!("Invalid pan {pan}, the range is -1.0..1.0");
+        }
+        if self.low_pass_hz == Some(0) {
+            bail!("Invalid low_pass_hz 0, the cutoff can't be zero");
+        }
+        Ok(())
+    }
+
+    pub fn apply<S>(&self, source: S) -> Box<dyn Source + Send>
+    where
+        S: Source + Send + 'static,
+    {
+        let mut source: Box<dyn Source + Send> = Box::new(source);
+        if let Some(frequency) = self.low_pass_hz {
+            source = Box::new(source.low_pass(frequency));
+        }
+        if let Some(max_duration) = self.max_duration_ms {
+            source = Box::new(
+                source.take_duration(Duration::from_millis(max_duration)),
+            );
+        }
+        if let Some(speed) = self.speed {
+            source = Box::new(source.speed(speed));
+        }
+        if let Some(volume) = self.volume {
+            source = Box::new(source.amplify(volume));
+        }
+        if let Some(pan) = self.pan {
+            source = Box::new(Balance::new(source, pan));
+        }
+        if let Some(delay) = self.delay_ms {
+            source = Box::new(source.delay(Duration::from_millis(delay)));
+        }
+        source
+    }
+}
+
+// pans by changing the volume of each channel, so the stereo clips keep their
+// stereo image, and the mono ones are played on both channels
+struct Balance<S: Source> {
+    source: UniformSourceIterator<S>,
+    gains: [f32; 2],
+    channel: usize,
+}
+
+impl<S: Source> Balance<S> {
+    fn new(source: S, pan: f32) -> Self {
+        let sample_rate = source.sample_rate();
+        // linear pan, the center keeps both channels at full volume
+        let left = (1.0 - pan).min(1.0);
+        let right = (1.0 + pan).min(1.0);
+        Self {
+            source: UniformSourceIterator::new(source, 2, sample_rate),
+            gains: [left, right],
+            channel: 0,
+        }
+    }
+}
+
+impl<S: Source> Iterator for Balance<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let sample = self.source.next()? * self.gains[self.channel];
+        self.channel ^= 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Balance<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        2
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// The clips and how to play them
+#[derive(Clone)]
+pub struct Sound {
+    pub pool: Arc<SoundPool>,
+    pub params: PlaybackParams,
+    pub mappings: Arc<[ParamMapping]>,
+    pub filter: Option<Arc<EventFilter>>,
+    pub scheduler: Arc<Scheduler>,
+}
+
+impl Sound {
+    pub fn load(config: &SoundConfig, cache: &mut SampleCache) -> Result<Self> {
+        let params = config.params();
+        params.validate()?;
+        let schedule = config.schedule();
+        schedule.validate()?;
+        for (i, mapping) in config.mappings().iter().enumerate() {
+            mapping
+                .validate()
+                .with_context(|| format!("Invalid map entry {i}"))?;
+        }
+        let filter = config.filter().map(EventFilter::new).transpose()?;
+        let pool = config.load_pool(cache)?;
+        Ok(Self {
+            pool: Arc::new(pool),
+            params,
+            mappings: config.mappings().into(),
+            filter: filter.map(Arc::new),
+            scheduler: Arc::new(Scheduler::new(schedule)),
+        })
+    }
+
+    /// The args are only needed for the filter and the mappings
+    pub fn needs_args(&self) -> bool {
+        self.filter.is_some() || !self.mappings.is_empty()
+    }
+
+    /// The playback parameters after applying the mappings
+    pub fn params(&self, args: Option<&EventArgs>) -> PlaybackParams {
+        let mut params = self.params;
+        if let Some(args) = args {
+            for mapping in self.mappings.iter() {
+                mapping.apply(args, &mut params);
+            }
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn config(yaml: &str) -> SoundConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn parse_forms() {
+        assert!(matches!(config("a.wav"), SoundConfig::Path(_)));
+        assert!(matches!(config("[a.wav, b.wav]"), SoundConfig::Pool(_)));
+        let detailed = config("{path: a.wav, volume: 0.5, cooldown_ms: 100}");
+        assert_eq!(detailed.params().volume, Some(0.5));
+        assert_eq!(detailed.schedule().cooldown_ms, Some(100));
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let error =
+            serde_yaml::from_str::<SoundConfig>("{path: a.wav, volum: 0.5}")
+                .err()
+                .unwrap();
+        assert!(error.to_string().contains("unknown field `volum`"));
+        // all the flattened fields are listed
+        let yaml = serde_yaml::to_string(&DetailedSoundConfig::default());
+        serde_yaml::from_str::<DetailedSoundConfig>(&yaml.unwrap()).unwrap();
+    }
+
+    #[test]
+    fn pool_errors_are_reported() {
+        let error =
+            serde_yaml::from_str::<SoundConfig>("[{path: a, wieght: 2}]")
+                .err()
+                .unwrap();
+        assert!(error.to_string().contains("unknown field `wieght`"));
+    }
+
+    #[test]
+    fn single_source() {
+        let mut cache = SampleCache::new(Default::default());
+        let load = |yaml, cache: &mut SampleCache| {
+            config(yaml).load_pool(cache).is_ok()
+        };
+        assert!(load("{path: builtin:add.wav}", &mut cache));
+        assert!(!load("{no_repeat: true}", &mut cache));
+        assert!(!load(
+            "{path: builtin:add.wav, pool: [builtin:remove.wav]}",
+            &mut cache
+        ));
+    }
+
+    #[test]
+    fn invalid_params() {
+        let invalid = |yaml| {
+            let params: PlaybackParams = serde_yaml::from_str(yaml).unwrap();
+            params.validate().is_err()
+        };
+        assert!(invalid("{volume: -1.0}"));
+        assert!(invalid("{speed: 0.0}"));
+        assert!(invalid("{pan: 1.5}"));
+        assert!(invalid("{low_pass_hz: 0}"));
+        assert!(!invalid("{volume: 0.0, speed: 2.0, pan: -1.0}"));
+    }
+
+    #[test]
+    fn apply_params() {
+        let params = PlaybackParams {
+            volume: Some(0.5),
+            pan: Some(1.0),
+            ..PlaybackParams::default()
+        };
+        let source = SamplesBuffer::new(1, 44100, vec![1.0; 2]);
+        let applied: Vec<f32> = params.apply(source).collect();
+        // only the right channel
+        assert_eq!(applied, [0.0, 0.5, 0.0, 0.5]);
+
+        // the stereo clips keep their channels
+        let params = PlaybackParams {
+            pan: Some(-0.5),
+            ..PlaybackParams::default()
+        };
+        let source = SamplesBuffer::new(2, 44100, vec![0.2, 1.0, 0.4, 0.0]);
+        let applied: Vec<f32> = params.apply(source).collect();
+        assert_eq!(applied, [0.2, 0.5, 0.4, 0.0]);
+
+        let params = PlaybackParams {
+            max_duration_ms: Some(1),
+            ..PlaybackParams::default()
+        };
+        let source = SamplesBuffer::new(1, 44100, vec![1.0; 1000]);
+        assert_eq!(params.apply(source).count(), 44);
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::cache::{ClipData, SampleCache};
+
+/// A file, or a glob pattern, that is part of the event pool
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum PoolEntry {
+    Path(PathBuf),
+    Weighted { path: PathBuf, weight: Option<f32> },
+}
+
+// not untagged, so a typo in a key is reported as such, instead of not
+// matching any variant
+impl<'de> Deserialize<'de> for PoolEntry {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Weighted {
+            path: PathBuf,
+            weight: Option<f32>,
+        }
+
+        struct EntryVisitor;
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = PoolEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a path, or a table with the path and weight")
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                path: &str,
+            ) -> Result<PoolEntry, E> {
+                Ok(PoolEntry::Path(path.into()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<PoolEntry, A::Error> {
+                let Weighted { path, weight } =
+                    Weighted::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(PoolEntry::Weighted { path, weight })
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+impl PoolEntry {
+    fn path(&self) -> &Path {
+        match self {
+            PoolEntry::Path(path) | PoolEntry::Weighted { path, .. } => path,
+        }
+    }
+
+    pub fn resolve_path(&mut self, base: &Path) {
+        let (PoolEntry::Path(path) | PoolEntry::Weighted { path, .. }) = self;
+        *path = resolve_path(base, path);
+    }
+
+    fn weight(&self) -> f32 {
+        match self {
+            PoolEntry::Path(_) => 1.0,
+            PoolEntry::Weighted { weight, .. } => weight.unwrap_or(1.0),
+        }
+    }
+}
+
+struct Clip {
+    data: ClipData,
+    weight: f32,
+}
+
+/// All the files an event can play, one is randomly picked each time
+pub struct SoundPool {
+    clips: Vec<Clip>,
+    // don't play the same file twice in a row
+    no_repeat: bool,
+    last: Mutex<Option<usize>>,
+}
+
+impl SoundPool {
+    pub fn load(
+        entries: &[PoolEntry],
+        no_repeat: bool,
+        cache: &mut SampleCache,
+    ) -> Result<Self> {
+        let mut clips = vec![];
+        for entry in entries {
+            let weight = entry.weight();
+            if !(weight > 0.0 && weight.is_finite()) {
+                bail!(
+                    "Invalid weight {weight}, it needs to be bigger than zero"
+                );
+            }
+            for path in expand_glob(entry.path())? {
+                clips.push(Clip {
+                    data: cache.load(&path)?,
+                    weight,
+                });
+            }
+        }
+        if clips.is_empty() {
+            bail!("No files in the sound pool");
+        }
+        Ok(Self {
+            clips,
+            no_repeat,
+            last: Mutex::new(None),
+        })
+    }
+
+    pub fn single(data: ClipData) -> Self {
+        Self {
+            clips: vec![Clip { data, weight: 1.0 }],
+            no_repeat: false,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Randomly select one of the files, based on their weights
+    pub fn pick(&self) -> ClipData {
+        let mut last = self.last.lock().unwrap();
+        let skip = (self.no_repeat && self.clips.len() > 1)
+            .then_some(*last)
+            .flatten();
+        let candidates = || {
+            self.clips
+                .iter()
+                .enumerate()
+                .filter(move |(i, _clip)| Some(*i) != skip)
+        };
+
+        let total: f32 = candidates().map(|(_i, clip)| clip.weight).sum();
+        let mut target = fastrand::f32() * total;
+        let mut picked = 0;
+        for (i, clip) in candidates() {
+            picked = i;
+            if target < clip.weight {
+                break;
+            }
+            target -= clip.weight;
+        }
+        *last = Some(picked);
+        self.clips[picked].data.clone()
+    }
+}
+
+/// Relative paths are relative to `base`, the absolute and built-in paths are
+/// kept as is
+pub fn resolve_path(base: &Path, path: &Path) -> PathBuf {
+    if crate::assets::is_builtin(path) {
+        path.to_owned()
+    } else {
+        base.join(path)
+    }
+}
+
+// paths with wildcards are expanded to all the matching files
+fn expand_glob(path: &Path) -> Result<Vec<PathBuf>> {
+    let Some(pattern) = path.to_str().filter(|x| x.contains(['*', '?', '[']))
+    else {
+        return Ok(vec![path.to_owned()]);
+    };
+    let paths = glob::glob(pattern)
+        .with_context(|| format!("Invalid pattern {pattern}"))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty() {
+        bail!("No files matching {pattern}");
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    // each clip is a single sample with its index, to know which was picked
+    fn pool(weights: &[f32], no_repeat: bool) -> SoundPool {
+        let clips = weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| Clip {
+                data: ClipData::Decoded(SamplesBuffer::new(
+                    1,
+                    44100,
+                    vec![i as f32],
+                )),
+                weight: *weight,
+            })
+            .collect();
+        SoundPool {
+            clips,
+            no_repeat,
+            last: Mutex::new(None),
+        }
+    }
+
+    fn pick(pool: &SoundPool) -> usize {
+        pool.pick().source().unwrap().next().unwrap() as usize
+    }
+
+    #[test]
+    fn weighted_pick() {
+        fastrand::seed(7);
+        let pool = pool(&[1.0, 3.0], false);
+        let mut picked = [0; 2];
+        for _ in 0..4000 {
+            picked[pick(&pool)] += 1;
+        }
+        // about 1000 and 3000
+        assert!((800..1200).contains(&picked[0]), "{picked:?}");
+        assert_eq!(picked[0] + picked[1], 4000);
+    }
+
+    #[test]
+    fn no_repeat() {
+        fastrand::seed(7);
+        let three = pool(&[1.0, 1.0, 1.0], true);
+        let mut last = pick(&three);
+        for _ in 0..100 {
+            let picked = pick(&three);
+            assert_ne!(picked, last);
+            last = picked;
+        }
+        // a single file is repeated anyway
+        let single = pool(&[1.0], true);
+        assert_eq!(pick(&single), 0);
+        assert_eq!(pick(&single), 0);
+    }
+
+    #[test]
+    fn parse_entries() {
+        let entries: Vec<PoolEntry> =
+            serde_yaml::from_str("[a.wav, {path: b.wav, weight: 2.0}]")
+                .unwrap();
+        assert_eq!(entries[0].weight(), 1.0);
+        assert_eq!(entries[1].path(), Path::new("b.wav"));
+        assert_eq!(entries[1].weight(), 2.0);
+        let error = serde_yaml::from_str::<PoolEntry>("{path: a, wieght: 2}")
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("unknown field `wieght`"));
+    }
+
+    #[test]
+    fn invalid_weight() {
+        let mut cache = SampleCache::new(Default::default());
+        let entry = PoolEntry::Weighted {
+            path: "builtin:startup.wav".into(),
+            weight: Some(0.0),
+        };
+        assert!(SoundPool::load(&[entry], false, &mut cache).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, bail};
+
+use glob::Pattern;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AudioConfig;
+
+/// The name of the top level files, used when no profile is selected
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// A named set of sounds, based on the top level files or another profile
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    /// the profile this one is based on, the top level files if not set
+    pub extends: Option<String>,
+    /// glob patterns of the inherited events that are not played
+    #[serde(default)]
+    pub mute: Vec<String>,
+    /// added to the inherited events, replacing the existing ones
+    #[serde(default)]
+    pub files: AudioConfig,
+}
+
+/// The files of the profile, including the inherited ones
+pub fn profile_files(
+    files: &AudioConfig,
+    profiles: &BTreeMap<String, ProfileConfig>,
+    name: &str,
+) -> Result<AudioConfig> {
+    if profiles.contains_key(DEFAULT_PROFILE) {
+        bail!("The profile name {DEFAULT_PROFILE} is reserved");
+    }
+    resolve(files, profiles, name, &mut vec![])
+}
+
+fn resolve<'a>(
+    files: &AudioConfig,
+    profiles: &'a BTreeMap<String, ProfileConfig>,
+    name: &'a str,
+    visited: &mut Vec<&'a str>,
+) -> Result<AudioConfig> {
+    if name == DEFAULT_PROFILE {
+        return Ok(files.clone());
+    }
+    if visited.contains(&name) {
+        bail!("Profile {name} extends itself");
+    }
+    visited.push(name);
+    let profile = profiles
+        .get(name)
+        .with_context(|| format!("Profile {name} not found"))?;
+
+    let base = profile.extends.as_deref().unwrap_or(DEFAULT_PROFILE);
+    let mut inherited = resolve(files, profiles, base, visited)?;
+    for pattern in &profile.mute {
+        let pattern = Pattern::new(pattern)
+            .with_context(|| format!("Invalid mute pattern {pattern}"))?;
+        if !inherited.mute(&pattern) {
+            warn!(&format!("Profile {name}: {pattern} matches no event"));
+        }
+    }
+    let mut files = profile.files.clone();
+    files.merge(inherited);
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::EventKind;
+
+    fn events(files: &AudioConfig) -> Vec<EventKind> {
+        files.iter().map(|(event, _)| event).collect()
+    }
+
+    fn profiles(yaml: &str) -> BTreeMap<String, ProfileConfig> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn files() -> AudioConfig {
+        serde_yaml::from_str(
+            "{function_added: a, function_updated: b, data_written: c}",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn default_profile() {
+        let resolved =
+            profile_files(&files(), &BTreeMap::new(), DEFAULT_PROFILE).unwrap();
+        assert_eq!(events(&resolved).len(), 3);
+    }
+
+    #[test]
+    fn extends_and_mute() {
+        let profiles = profiles(
+            "
+            quiet: {mute: ['*_updated', 'data_*']}
+            loud: {extends: quiet, files: {tag_added: d}}
+            ",
+        );
+        let resolved = profile_files(&files(), &profiles, "loud").unwrap();
+        assert_eq!(
+            events(&resolved),
+            [EventKind::FunctionAdded, EventKind::TagAdded]
+        );
+    }
+
+    #[test]
+    fn invalid_profiles() {
+        let cycle = profiles("{a: {extends: b}, b: {extends: a}}");
+        assert!(profile_files(&files(), &cycle, "a").is_err());
+        assert!(profile_files(&files(), &cycle, "missing").is_err());
+        let reserved = profiles("default: {}");
+        assert!(profile_files(&files(), &reserved, "default").is_err());
+    }
+}
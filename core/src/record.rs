@@ -0,0 +1,542 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use rodio::source::{SeekError, UniformSourceIterator};
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+const SAMPLE_RATE: u32 = 44_100;
+const CHANNELS: u16 = 2;
+// the samples a tee collects before sending them, about 50 ms
+const CHUNK_LEN: usize = 4096;
+// how often the file is made valid, in case the process is killed
+const FLUSH_TIME: Duration = Duration::from_secs(5);
+
+enum Message {
+    // rendered by the recorder thread, when not played in real time
+    Sound {
+        offset: Duration,
+        name: String,
+        source: Box<dyn Source + Send>,
+    },
+    // a tee started playing
+    Start {
+        id: u64,
+        offset: Duration,
+        name: String,
+    },
+    Samples(u64, Vec<f32>),
+    End(u64),
+    // write everything before the offset, and make the file valid so far
+    Flush(Duration, Sender<Result<()>>),
+    Stop,
+}
+
+/// Mix the played sounds into a WAV file, at the time they were played. The
+/// sounds are mixed in the background and written as soon as no later sound
+/// can overlap them, so only the sounds still playing are kept in memory.
+pub struct Recorder {
+    start: Instant,
+    path: PathBuf,
+    // the file is only kept if moved with save_as
+    temporary: bool,
+    next_id: AtomicU64,
+    sender: Option<Sender<Message>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl Recorder {
+    /// Record into the WAV file at `path`, and the event timestamps as
+    /// Audacity labels, at the same path with the txt extension
+    pub fn create(path: &Path) -> Result<Self> {
+        Self::open(path, true)
+    }
+
+    /// Same as create, for the sounds added with add_at faster than they
+    /// play, eg: when replaying a trace
+    pub fn offline(path: &Path) -> Result<Self> {
+        Self::open(path, false)
+    }
+
+    // the live recordings are written up to the current time periodically,
+    // the offline ones only up to the last sound
+    fn open(path: &Path, live: bool) -> Result<Self> {
+        let start = Instant::now();
+        let mixdown = Mixdown::create(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let clock = live.then_some(start);
+        let thread = std::thread::Builder::new()
+            .name("BinJuice recorder".to_string())
+            .spawn(move || mixdown.run(receiver, clock))
+            .context("Unable to spawn the recorder thread")?;
+        Ok(Self {
+            start,
+            path: path.to_owned(),
+            temporary: false,
+            next_id: AtomicU64::new(0),
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Record into a temporary file, kept with save_as
+    pub fn temporary() -> Result<Self> {
+        let path = std::env::temp_dir()
+            .join(format!("binjuice-recording-{}.wav", std::process::id()));
+        let mut recorder = Self::create(&path)?;
+        recorder.temporary = true;
+        Ok(recorder)
+    }
+
+    /// Record the source while it plays, starting at the current time. The
+    /// samples are sent to the recorder as the returned source is played.
+    pub fn tee<S: Source>(&self, name: &str, source: S) -> Tee<S> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = Message::Start {
+            id,
+            offset: self.start.elapsed(),
+            name: name.to_string(),
+        };
+        self.send(message);
+        Tee {
+            source: UniformSourceIterator::new(source, CHANNELS, SAMPLE_RATE),
+            id,
+            chunk: Vec::with_capacity(CHUNK_LEN),
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Mix the sound at the current time, for the sounds that are not played
+    pub fn add<S>(&self, name: &str, source: S)
+    where
+        S: Source + Send + 'static,
+    {
+        self.add_at(self.start.elapsed(), name, source);
+    }
+
+    /// Mix the sound at `offset` from the start of the recording, the sounds
+    /// need to be added in order
+    pub fn add_at<S>(&self, offset: Duration, name: &str, source: S)
+    where
+        S: Source + Send + 'static,
+    {
+        self.send(Message::Sound {
+            offset,
+            name: name.to_string(),
+            source: Box::new(source),
+        });
+    }
+
+    fn send(&self, message: Message) {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(message).is_ok());
+        if !sent {
+            err!("The recorder stopped");
+        }
+    }
+
+    /// Write the recording so far, lasting until now, it continues after that
+    pub fn flush(&self) -> Result<()> {
+        let (result, receiver) = mpsc::channel();
+        self.sender
+            .as_ref()
+            .context("The recorder stopped")?
+            .send(Message::Flush(self.start.elapsed(), result))
+            .map_err(|_| anyhow!("The recorder stopped"))?;
+        receiver.recv().context("The recorder stopped")?
+    }
+
+    /// Write the rest of the sounds and close the file
+    pub fn finish(mut self) -> Result<()> {
+        self.flush()?;
+        self.stop()
+    }
+
+    /// Finish the recording, and move it to `path`
+    pub fn save_as(mut self, path: &Path) -> Result<()> {
+        self.flush()?;
+        self.stop()?;
+        self.temporary = false;
+        move_file(&self.path, path)?;
+        move_file(&labels_path(&self.path), &labels_path(path))
+    }
+
+    // the tees may still be playing, so they can't keep the thread running
+    fn stop(&mut self) -> Result<()> {
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(Message::Stop);
+        }
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| anyhow!("The recorder thread panicked"))?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            err!(&format!("Unable to write the recording: {e:#}"));
+        }
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+            let _ = std::fs::remove_file(labels_path(&self.path));
+        }
+    }
+}
+
+/// A source being played and recorded, see Recorder::tee
+pub struct Tee<S: Source> {
+    source: UniformSourceIterator<S>,
+    id: u64,
+    // the samples played since the last chunk was sent
+    chunk: Vec<f32>,
+    // None once the recorder stopped
+    sender: Option<Sender<Message>>,
+}
+
+impl<S: Source> Tee<S> {
+    fn send_chunk(&mut self) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let chunk =
+            std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_LEN));
+        if sender.send(Message::Samples(self.id, chunk)).is_err() {
+            self.sender = None;
+        }
+    }
+
+    fn end(&mut self) {
+        self.send_chunk();
+        if let Some(sender) = self.sender.take() {
+            let _ = sender.send(Message::End(self.id));
+        }
+    }
+}
+
+impl<S: Source> Iterator for Tee<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let Some(sample) = self.source.next() else {
+            self.end();
+            return None;
+        };
+        if self.sender.is_some() {
+            self.chunk.push(sample);
+            if self.chunk.len() >= CHUNK_LEN {
+                self.send_chunk();
+            }
+        }
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Tee<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+// dropped before the end, eg: the output stream was replaced
+impl<S: Source> Drop for Tee<S> {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
+
+fn labels_path(path: &Path) -> PathBuf {
+    path.with_extension("txt")
+}
+
+// the temporary dir may be in another file system
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to)
+        .with_context(|| format!("Unable to write {}", to.display()))?;
+    std::fs::remove_file(from)?;
+    Ok(())
+}
+
+// owned by the recorder thread
+struct Mixdown {
+    writer: WavWriter<BufWriter<File>>,
+    labels: BufWriter<File>,
+    // the samples already written
+    written: usize,
+    // the samples after that, still being mixed
+    mix: Vec<f32>,
+    // where the next samples of each tee still playing go
+    playing: BTreeMap<u64, usize>,
+}
+
+impl Mixdown {
+    fn create(path: &Path) -> Result<Self> {
+        let spec = WavSpec {
+            channels: CHANNELS,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(path, spec)
+            .with_context(|| format!("Unable to create {}", path.display()))?;
+        let labels_path = labels_path(path);
+        let labels = File::create(&labels_path).with_context(|| {
+            format!("Unable to create {}", labels_path.display())
+        })?;
+        Ok(Self {
+            writer,
+            labels: BufWriter::new(labels),
+            written: 0,
+            mix: vec![],
+            playing: BTreeMap::new(),
+        })
+    }
+
+    fn run(
+        mut self,
+        receiver: Receiver<Message>,
+        clock: Option<Instant>,
+    ) -> Result<()> {
+        // after an error the messages are still received, so the flushes
+        // get the error, and the tees keep playing
+        let mut failed = None;
+        let mut next_flush = Instant::now() + FLUSH_TIME;
+        loop {
+            let timeout = next_flush.saturating_duration_since(Instant::now());
+            let result = match receiver.recv_timeout(timeout) {
+                Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => {
+                    break;
+                }
+                Ok(Message::Flush(until, result)) => {
+                    let flushed = match &failed {
+                        Some(e) => Err(anyhow!("{e:#}")),
+                        None => self.flush(until),
+                    };
+                    let _ = result.send(flushed);
+                    Ok(())
+                }
+                Ok(message) if failed.is_none() => self.handle(message),
+                Ok(_) | Err(RecvTimeoutError::Timeout) => Ok(()),
+            };
+            // checked after every message, so it's not delayed by a burst
+            let result = result.and_then(|()| {
+                if failed.is_some() || Instant::now() < next_flush {
+                    return Ok(());
+                }
+                next_flush = Instant::now() + FLUSH_TIME;
+                self.flush(
+                    clock.map_or(Duration::ZERO, |start| start.elapsed()),
+                )
+            });
+            if let Err(e) = result {
+                err!(&format!("Unable to write the recording: {e:#}"));
+                failed = Some(e);
+            }
+        }
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        // the tees still playing are cut at the end of the recording
+        self.playing.clear();
+        let end = self.written + self.mix.len();
+        self.write_until(end)?;
+        self.labels.flush()?;
+        self.writer.finalize()?;
+        Ok(())
+    }
+
+    fn handle(&mut self, message: Message) -> Result<()> {
+        match message {
+            Message::Sound {
+                offset,
+                name,
+                source,
+            } => {
+                let start = self.start(offset, &name)?;
+                let rendered: Vec<f32> =
+                    UniformSourceIterator::new(source, CHANNELS, SAMPLE_RATE)
+                        .collect();
+                self.mix_at(start, &rendered);
+            }
+            Message::Start { id, offset, name } => {
+                let start = self.start(offset, &name)?;
+                self.playing.insert(id, start);
+            }
+            Message::Samples(id, samples) => {
+                if let Some(&position) = self.playing.get(&id) {
+                    self.mix_at(position, &samples);
+                    self.playing.insert(id, position + samples.len());
+                }
+            }
+            Message::End(id) => {
+                self.playing.remove(&id);
+            }
+            Message::Flush(..) | Message::Stop => {}
+        }
+        Ok(())
+    }
+
+    // a sound can't start before the samples already written
+    fn start(&mut self, offset: Duration, name: &str) -> Result<usize> {
+        let start = samples(offset).max(self.written);
+        self.write_until(start)?;
+        let offset = offset.as_secs_f64();
+        writeln!(self.labels, "{offset:.6}\t{offset:.6}\t{name}")?;
+        Ok(start)
+    }
+
+    fn mix_at(&mut self, position: usize, samples: &[f32]) {
+        let start = position - self.written;
+        if self.mix.len() < start + samples.len() {
+            self.mix.resize(start + samples.len(), 0.0);
+        }
+        for (mixed, sample) in self.mix[start..].iter_mut().zip(samples) {
+            *mixed += sample;
+        }
+    }
+
+    fn flush(&mut self, until: Duration) -> Result<()> {
+        self.write_until(samples(until))?;
+        self.labels.flush()?;
+        // updates the header, so the file is valid so far
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // write the mixed samples before `end`, silence past the mix. The tees
+    // still playing send their samples later, so it stops before them.
+    fn write_until(&mut self, end: usize) -> Result<()> {
+        let end = self.playing.values().fold(end, |end, &x| end.min(x));
+        let Some(len) = end.checked_sub(self.written) else {
+            return Ok(());
+        };
+        let mixed = self.mix.len().min(len);
+        let samples = self
+            .mix
+            .drain(..mixed)
+            .chain(std::iter::repeat_n(0.0, len - mixed));
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            self.writer.write_sample(sample)?;
+        }
+        self.written = end;
+        Ok(())
+    }
+}
+
+// the interleaved samples before the offset
+fn samples(offset: Duration) -> usize {
+    let frames = (offset.as_secs_f64() * f64::from(SAMPLE_RATE)).round();
+    frames as usize * usize::from(CHANNELS)
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    #[test]
+    fn mix_at_offset() {
+        let path = std::env::temp_dir()
+            .join(format!("binjuice-test-{}.wav", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        let offset = Duration::from_millis(10);
+        let mono = SamplesBuffer::new(1, SAMPLE_RATE, vec![0.25; 4]);
+        recorder.add_at(offset, "a", mono.clone());
+        recorder.add_at(offset, "b", mono);
+        recorder.finish().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, CHANNELS);
+        let written: Vec<i16> =
+            reader.samples().map(|sample| sample.unwrap()).collect();
+        let start = samples(offset);
+        assert!(written[..start].iter().all(|x| *x == 0));
+        // rendered as stereo, and mixed
+        let mixed = (0.5 * f32::from(i16::MAX)) as i16;
+        assert_eq!(written[start..start + 8], [mixed; 8]);
+        let labels = std::fs::read_to_string(labels_path(&path)).unwrap();
+        assert_eq!(labels.lines().count(), 2);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(labels_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn tee_records_while_playing() {
+        let path = std::env::temp_dir()
+            .join(format!("binjuice-tee-{}.wav", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        let len = CHUNK_LEN + 100;
+        let mono = SamplesBuffer::new(1, SAMPLE_RATE, vec![0.25; len]);
+        let mut tee = recorder.tee("a", mono);
+        // played as stereo, the first chunk is sent to the recorder
+        let played: Vec<f32> = tee.by_ref().take(CHUNK_LEN + 1).collect();
+        assert!(played.iter().all(|sample| *sample == 0.25));
+        // can't write past the sound still playing
+        recorder.flush().unwrap();
+        assert_eq!(tee.by_ref().count(), len * 2 - CHUNK_LEN - 1);
+        drop(tee);
+        recorder.finish().unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let written: Vec<i16> =
+            reader.samples().map(|sample| sample.unwrap()).collect();
+        let recorded = (0.25 * f32::from(i16::MAX)) as i16;
+        let count = written.iter().filter(|x| **x == recorded).count();
+        assert_eq!(count, len * 2);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(labels_path(&path)).unwrap();
+    }
+
+    #[test]
+    fn stop_while_playing() {
+        let recorder = Recorder::temporary().unwrap();
+        let path = recorder.path.clone();
+        let mono = SamplesBuffer::new(1, SAMPLE_RATE, vec![0.25; 4]);
+        let mut tee = recorder.tee("a", mono);
+        tee.next();
+        // the tee doesn't keep the recorder running
+        recorder.flush().unwrap();
+        drop(recorder);
+        assert!(!path.exists());
+        assert_eq!(tee.count(), 7);
+    }
+}
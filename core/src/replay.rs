@@ -0,0 +1,259 @@
+//! Replay a trace written by the `trace` option, without Binary Ninja
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+
+use rodio::Source;
+
+use crate::audio::AudioFiles;
+use crate::cache::SampleCache;
+use crate::config::Config;
+use crate::output::{FallbackConfig, Output};
+use crate::profile::{self, DEFAULT_PROFILE};
+use crate::record::Recorder;
+use crate::trace::TraceEvent;
+
+/// How to replay a trace
+pub struct ReplayOptions {
+    pub config: PathBuf,
+    /// the directory with the sound packs used by the config
+    pub packs: Option<PathBuf>,
+    /// used instead of the config one
+    pub profile: Option<String>,
+    /// 2.0 is twice as fast
+    pub speed: f64,
+    /// the silences longer than this are shortened, in seconds
+    pub max_gap: f64,
+    /// only replay the events of this view session
+    pub session: Option<usize>,
+    /// used instead of the config one
+    pub device: Option<String>,
+    /// write the sounds to this WAV file instead of playing them
+    pub wav: Option<PathBuf>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            config: PathBuf::from("binjuice.yml"),
+            packs: None,
+            profile: None,
+            speed: 1.0,
+            max_gap: 5.0,
+            session: None,
+            device: None,
+            wav: None,
+        }
+    }
+}
+
+impl ReplayOptions {
+    pub fn check(&self) -> Result<()> {
+        let Self { speed, max_gap, .. } = *self;
+        if !(speed > 0.0 && f64::is_finite(speed)) {
+            bail!("Invalid speed {speed}, it needs to be bigger than zero");
+        }
+        if !(max_gap >= 0.0 && f64::is_finite(max_gap)) {
+            bail!("Invalid max gap {max_gap}, it can't be negative");
+        }
+        Ok(())
+    }
+}
+
+/// Play the events of the trace file, using the sounds of the config
+pub fn replay(trace: &Path, options: ReplayOptions) -> Result<()> {
+    options.check()?;
+    let mut config = Config::open(&options.config)?;
+    let packs_dir = match (&config.pack, options.packs) {
+        (Some(_), None) => {
+            bail!("The config uses a sound pack, set the --packs directory")
+        }
+        (_, packs) => packs.unwrap_or_default(),
+    };
+    config.merge_sounds(&packs_dir)?;
+    let profile = options
+        .profile
+        .as_deref()
+        .or(config.profile.as_deref())
+        .unwrap_or(DEFAULT_PROFILE);
+    let files =
+        profile::profile_files(&config.files, &config.profiles, profile)?;
+    let mut cache = SampleCache::new(config.cache);
+    let audio = AudioFiles::load(&files, &mut cache)?;
+
+    let file = File::open(trace)
+        .with_context(|| format!("Unable to open {}", trace.display()))?;
+    let events =
+        read_trace(BufReader::new(file), options.session, options.max_gap)?;
+    info!(&format!("Replaying {} events", events.len()));
+    match &options.wav {
+        Some(path) => replay_wav(&audio, events, options.speed, path),
+        None => {
+            let device = options.device.or(config.device);
+            let output = Output::open(device, &FallbackConfig::default())?;
+            replay_output(&audio, events, options.speed, &output);
+            Ok(())
+        }
+    }
+}
+
+// the events, with the offset from the first one
+fn read_trace(
+    reader: impl BufRead,
+    session: Option<usize>,
+    max_gap: f64,
+) -> Result<Vec<(Duration, TraceEvent)>> {
+    let mut events = vec![];
+    let mut offset = 0.0;
+    let mut last_time: Option<f64> = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: TraceEvent = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid event at line {}", i + 1))?;
+        if session.is_some()
+            && event.view.as_ref().map(|view| view.session) != session
+        {
+            continue;
+        }
+        // the file is appended by every session, skip the time between them
+        if let Some(last_time) = last_time {
+            offset += (event.time - last_time).clamp(0.0, max_gap);
+        }
+        last_time = Some(event.time);
+        events.push((Duration::from_secs_f64(offset), event));
+    }
+    Ok(events)
+}
+
+// the scheduler receives the original times, so the same events are dropped
+// at any speed
+fn replay_output(
+    audio: &AudioFiles,
+    events: Vec<(Duration, TraceEvent)>,
+    speed: f64,
+    output: &Output,
+) {
+    let start = Instant::now();
+    let mut end = start;
+    for (offset, event) in events {
+        let play_at = start + offset.div_f64(speed);
+        std::thread::sleep(play_at.saturating_duration_since(Instant::now()));
+        let kind = event.event;
+        let Some(sound) =
+            audio.admit(kind, start + offset, 1.0, || event.into_args())
+        else {
+            continue;
+        };
+        let Some(source) = sound.decode() else {
+            continue;
+        };
+        let duration = source.total_duration().unwrap_or_default();
+        end = end.max(Instant::now() + duration);
+        output.play_source(kind.name(), || Some(source));
+    }
+    // let the last sounds finish
+    std::thread::sleep(end.saturating_duration_since(Instant::now()));
+}
+
+fn replay_wav(
+    audio: &AudioFiles,
+    events: Vec<(Duration, TraceEvent)>,
+    speed: f64,
+    path: &Path,
+) -> Result<()> {
+    let start = Instant::now();
+    let recorder = Recorder::offline(path)?;
+    // the sounds are rendered as fast as possible, so their voices are held
+    // until they would have ended, for max_voices
+    let mut playing = vec![];
+    for (offset, event) in events {
+        let offset_at_speed = offset.div_f64(speed);
+        playing.retain(|(end, _voice)| *end > offset_at_speed);
+        let kind = event.event;
+        let Some(sound) =
+            audio.admit(kind, start + offset, 1.0, || event.into_args())
+        else {
+            continue;
+        };
+        let Some(source) = sound.decode() else {
+            continue;
+        };
+        let duration = source.total_duration();
+        let (source, voice) = source.detach();
+        if let (Some(duration), Some(voice)) = (duration, voice) {
+            playing.push((offset_at_speed + duration, voice));
+        }
+        recorder.add_at(offset_at_speed, kind.name(), source);
+    }
+    recorder.finish()?;
+    info!(&format!("Replay written to {}", path.display()));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::EventKind;
+    use crate::filter::EventFilter;
+
+    const TRACE: &str = r#"
+{"time":100.0,"event":"function_added","view":{"session":1,"file":"a"},"args":{"address":16}}
+{"time":100.5,"event":"function_added","view":{"session":2,"file":"b"},"args":{}}
+
+{"time":3700.0,"event":"end_binary_ninja","view":null,"args":{}}
+"#;
+
+    #[test]
+    fn view_info_for_the_filters_and_mappings() {
+        let trace = r#"{"time":1.0,"event":"function_added","view":null,"view_info":{"range":{"start":0,"end":256},"sections":[".text"],"permissions":5},"args":{"address":64}}"#;
+        let (_, event) = read_trace(trace.as_bytes(), None, 5.0)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let args = event.into_args();
+        let config = serde_yaml::from_str("section: [.text]").unwrap();
+        let filter = EventFilter::new(&config).unwrap();
+        assert!(filter.matches(&args));
+        let view = args.view.unwrap();
+        assert_eq!(view.address_range(), 0..256);
+        assert_eq!(view.permissions_at(64), Some(5));
+    }
+
+    fn offsets(events: &[(Duration, TraceEvent)]) -> Vec<f64> {
+        events
+            .iter()
+            .map(|(offset, _)| offset.as_secs_f64())
+            .collect()
+    }
+
+    #[test]
+    fn long_gaps_are_shortened() {
+        let events = read_trace(TRACE.as_bytes(), None, 5.0).unwrap();
+        assert_eq!(offsets(&events), [0.0, 0.5, 5.5]);
+        assert_eq!(events[0].1.event, EventKind::FunctionAdded);
+        assert_eq!(events[0].1.args.address, Some(16));
+        assert_eq!(events[2].1.event, EventKind::EndBinaryNinja);
+    }
+
+    #[test]
+    fn filter_by_session() {
+        let events = read_trace(TRACE.as_bytes(), Some(2), 5.0).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].1.view.as_ref().unwrap().file, "b");
+    }
+
+    #[test]
+    fn invalid_line() {
+        let trace = "{\"time\":1.0,\"event\":\"nope\",\"args\":{}}\n";
+        let err = read_trace(trace.as_bytes(), None, 5.0).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid event at line 1");
+    }
+}
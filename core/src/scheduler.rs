@@ -0,0 +1,267 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+
+use rodio::source::SeekError;
+use rodio::{ChannelCount, Sample, SampleRate, Source};
+
+use serde::{Deserialize, Serialize};
+
+/// Limits how often an event can produce a sound
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+pub struct ScheduleParams {
+    /// minimum time between two sounds
+    pub cooldown_ms: Option<u64>,
+    /// maximum number of sounds playing at the same time
+    pub max_voices: Option<usize>,
+    /// play a single sound for a burst of events
+    pub coalesce: Option<CoalesceParams>,
+}
+
+/// Only the first of `count` events received inside `window_ms` is played
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoalesceParams {
+    pub count: u32,
+    pub window_ms: u64,
+}
+
+impl ScheduleParams {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_voices == Some(0) {
+            bail!("Invalid max_voices 0, it needs to be at least 1");
+        }
+        if let Some(coalesce) = &self.coalesce
+            && coalesce.count == 0
+        {
+            bail!("Invalid coalesce count 0, it needs to be at least 1");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct State {
+    last_play: Option<Instant>,
+    // start of the current coalesce window and how many events it received
+    window_start: Option<Instant>,
+    window_events: u32,
+}
+
+/// Decides if an event will play, sits between the notifications and the mixer
+pub struct Scheduler {
+    params: ScheduleParams,
+    state: Mutex<State>,
+    voices: Arc<AtomicUsize>,
+}
+
+impl Scheduler {
+    pub fn new(params: ScheduleParams) -> Self {
+        Self {
+            params,
+            state: Mutex::new(State::default()),
+            voices: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Check if the event received at `now` should play
+    pub fn admit(&self, now: Instant) -> bool {
+        if let Some(max_voices) = self.params.max_voices
+            && self.voices.load(Ordering::Relaxed) >= max_voices
+        {
+            return false;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        if let (Some(cooldown), Some(last_play)) =
+            (self.params.cooldown_ms, state.last_play)
+            && now.saturating_duration_since(last_play)
+                < Duration::from_millis(cooldown)
+        {
+            return false;
+        }
+
+        if let Some(coalesce) = &self.params.coalesce {
+            let window = Duration::from_millis(coalesce.window_ms);
+            match state.window_start {
+                Some(start)
+                    if now.saturating_duration_since(start) < window
+                        && state.window_events < coalesce.count =>
+                {
+                    // part of a burst that already played
+                    state.window_events += 1;
+                    return false;
+                }
+                _ => {
+                    state.window_start = Some(now);
+                    state.window_events = 1;
+                }
+            }
+        }
+
+        state.last_play = Some(now);
+        true
+    }
+
+    /// Count the source as a playing voice until it finishes or is dropped
+    pub fn track<S: Source>(&self, source: S) -> Voice<S> {
+        Voice::new(source, &self.voices)
+    }
+}
+
+/// A playing voice, released when dropped
+pub struct VoiceGuard(Arc<AtomicUsize>);
+
+impl Drop for VoiceGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A source counted as a playing voice, released when it ends, or when it's
+/// dropped before, eg: when the output stream is replaced
+pub struct Voice<S> {
+    source: S,
+    guard: Option<VoiceGuard>,
+}
+
+impl<S> Voice<S> {
+    /// Count the source in `voices` until it finishes or is dropped
+    pub fn new(source: S, voices: &Arc<AtomicUsize>) -> Self {
+        voices.fetch_add(1, Ordering::Relaxed);
+        Self {
+            source,
+            guard: Some(VoiceGuard(Arc::clone(voices))),
+        }
+    }
+
+    /// Separate the voice from the source, for when the source is not played
+    /// in real time, eg: when writing a WAV file
+    pub fn detach(self) -> (S, Option<VoiceGuard>) {
+        (self.source, self.guard)
+    }
+}
+
+impl<S: Source> Iterator for Voice<S> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let sample = self.source.next();
+        if sample.is_none() {
+            self.guard = None;
+        }
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Voice<S> {
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+
+    fn channels(&self) -> ChannelCount {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.source.try_seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn cooldown() {
+        let scheduler = Scheduler::new(ScheduleParams {
+            cooldown_ms: Some(100),
+            ..ScheduleParams::default()
+        });
+        let start = Instant::now();
+        assert!(scheduler.admit(start));
+        assert!(!scheduler.admit(ms(start, 50)));
+        assert!(scheduler.admit(ms(start, 100)));
+        assert!(!scheduler.admit(ms(start, 150)));
+    }
+
+    #[test]
+    fn coalesce_bursts() {
+        let scheduler = Scheduler::new(ScheduleParams {
+            coalesce: Some(CoalesceParams {
+                count: 3,
+                window_ms: 100,
+            }),
+            ..ScheduleParams::default()
+        });
+        let start = Instant::now();
+        let played: Vec<bool> = [0, 10, 20, 30, 40, 200]
+            .into_iter()
+            .map(|offset| scheduler.admit(ms(start, offset)))
+            .collect();
+        // the fourth event is past the count, so it starts a new burst
+        assert_eq!(played, [true, false, false, true, false, true]);
+    }
+
+    #[test]
+    fn max_voices_until_finished() {
+        let scheduler = Scheduler::new(ScheduleParams {
+            max_voices: Some(1),
+            ..ScheduleParams::default()
+        });
+        let now = Instant::now();
+        assert!(scheduler.admit(now));
+        let voice = scheduler.track(SamplesBuffer::new(1, 44100, vec![0.0]));
+        assert!(!scheduler.admit(now));
+        // the voice is released once the sound ends
+        assert_eq!(voice.count(), 1);
+        assert!(scheduler.admit(now));
+    }
+
+    #[test]
+    fn max_voices_until_dropped() {
+        let scheduler = Scheduler::new(ScheduleParams {
+            max_voices: Some(1),
+            ..ScheduleParams::default()
+        });
+        let now = Instant::now();
+        let mut voice =
+            scheduler.track(SamplesBuffer::new(1, 44100, vec![0.0; 10]));
+        assert!(!scheduler.admit(now));
+        // dropped before the end, eg: the stream was replaced
+        voice.next();
+        drop(voice);
+        assert!(scheduler.admit(now));
+        assert_eq!(scheduler.voices.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn validate() {
+        let params = ScheduleParams {
+            max_voices: Some(0),
+            ..ScheduleParams::default()
+        };
+        assert!(params.validate().is_err());
+        assert!(ScheduleParams::default().validate().is_ok());
+    }
+}
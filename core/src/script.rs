@@ -0,0 +1,457 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow};
+
+use rhai::{AST, CallFnOptions, Dynamic, Engine, Scope};
+
+use crate::audio::{AudioFiles, AudioSink, PendingSound};
+use crate::cache::{CacheConfig, SampleCache};
+use crate::event::{Event, EventKind};
+use crate::playback::{Sound, SoundConfig};
+use crate::sink::{EventSink, Interest};
+
+// stop a script that takes too long, eg: an infinite loop, instead of
+// blocking the analysis
+const MAX_OPERATIONS: u64 = 1_000_000;
+// the files played by name that are kept loaded, or their load errors
+const MAX_SOUNDS: usize = 256;
+
+/// A sound requested by a script
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayRequest {
+    /// event name or file
+    pub name: String,
+    pub volume: f32,
+    /// speed multiplier, this also changes the pitch
+    pub pitch: f32,
+    pub delay_ms: u64,
+}
+
+/// The user event handlers, one Rhai function per event, eg:
+/// `fn function_added(args)`
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    handlers: BTreeSet<EventKind>,
+    // the `this` of the handlers, kept between calls
+    state: Mutex<Dynamic>,
+    // filled by the play functions while a handler runs
+    requests: Arc<Mutex<Vec<PlayRequest>>>,
+    // the files played by name, relative to the script
+    dir: PathBuf,
+    sounds: Mutex<(SampleCache, BTreeMap<String, Option<Sound>>)>,
+}
+
+impl Script {
+    pub fn load(path: &Path, cache: CacheConfig) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("")).to_owned();
+        Self::new(&source, dir, cache)
+    }
+
+    fn new(source: &str, dir: PathBuf, cache: CacheConfig) -> Result<Self> {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let engine = engine(&requests);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| anyhow!("Invalid script: {e}"))?;
+        let mut handlers = BTreeSet::new();
+        for function in ast.iter_functions() {
+            let Some(event) = EventKind::from_name(function.name) else {
+                continue;
+            };
+            if function.params.len() != 1 {
+                return Err(anyhow!(
+                    "The {event} handler needs to take only the args"
+                ));
+            }
+            handlers.insert(event);
+        }
+        let script = Self {
+            engine,
+            ast,
+            handlers,
+            state: Mutex::new(Dynamic::from_map(rhai::Map::new())),
+            requests,
+            dir,
+            sounds: Mutex::new((SampleCache::new(cache), BTreeMap::new())),
+        };
+        script
+            .engine
+            .run_ast(&script.ast)
+            .map_err(|e| anyhow!("Script error: {e}"))?;
+        if script
+            .ast
+            .iter_functions()
+            .any(|function| function.name == "init")
+        {
+            script.call("init", ())?;
+        }
+        Ok(script)
+    }
+
+    pub fn handles(&self, kind: EventKind) -> bool {
+        self.handlers.contains(&kind)
+    }
+
+    /// Run the event handler, and return the sounds it requested
+    pub fn run(&self, event: &Event) -> Vec<PlayRequest> {
+        let kind = event.kind();
+        if !self.handles(kind) {
+            return vec![];
+        }
+        let args = match rhai::serde::to_dynamic(event.args()) {
+            Ok(args) => args,
+            Err(e) => {
+                err!(&format!("Unable to convert the {kind} args: {e}"));
+                return vec![];
+            }
+        };
+        let mut args = args.cast::<rhai::Map>();
+        // the sections are not part of the args, but useful for filtering
+        if let (Some(view), Some(address)) =
+            (&event.args().view, event.args().address)
+        {
+            let sections: rhai::Array = view
+                .sections_at(address)
+                .into_iter()
+                .map(Dynamic::from)
+                .collect();
+            args.insert("sections".into(), sections.into());
+        }
+        match self.call(kind.name(), (args,)) {
+            Ok(requests) => requests,
+            Err(e) => {
+                err!(&format!("{e:#}"));
+                vec![]
+            }
+        }
+    }
+
+    // the state lock also keeps the requests of each call apart
+    fn call(
+        &self,
+        name: &str,
+        args: impl rhai::FuncArgs,
+    ) -> Result<Vec<PlayRequest>> {
+        let mut state = self.state.lock().unwrap();
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut state);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &self.ast,
+            name,
+            args,
+        );
+        let requests = std::mem::take(&mut *self.requests.lock().unwrap());
+        match result {
+            Ok(_) => Ok(requests),
+            Err(e) => Err(anyhow!("Script error at {name}: {e}")),
+        }
+    }
+
+    // a file played by a script, loaded once. The names come from the
+    // script, so only MAX_SOUNDS are kept, the rest are loaded every time.
+    fn sound(&self, name: &str) -> Option<Sound> {
+        let mut sounds = self.sounds.lock().unwrap();
+        let (cache, sounds) = &mut *sounds;
+        if let Some(sound) = sounds.get(name) {
+            return sound.clone();
+        }
+        let mut config = SoundConfig::Path(name.into());
+        config.resolve_paths(&self.dir);
+        // the failures are kept too, so they are only logged once
+        let sound = Sound::load(&config, cache)
+            .inspect_err(|e| err!(&format!("Unable to load {name}: {e:#}")))
+            .ok();
+        if sounds.len() < MAX_SOUNDS {
+            sounds.insert(name.to_string(), sound.clone());
+        }
+        sound
+    }
+}
+
+fn engine(requests: &Arc<Mutex<Vec<PlayRequest>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.on_print(|msg| info!(msg));
+    engine.on_debug(|msg, _source, position| {
+        log_dbg!(&format!("{position}: {msg}"));
+    });
+
+    let play = {
+        let requests = Arc::clone(requests);
+        move |name: &str, volume: f64, pitch: f64, delay_ms: i64| {
+            requests.lock().unwrap().push(PlayRequest {
+                name: name.to_string(),
+                volume: volume as f32,
+                pitch: pitch as f32,
+                delay_ms: delay_ms.max(0) as u64,
+            });
+        }
+    };
+    let play = Arc::new(play);
+    let p = Arc::clone(&play);
+    engine.register_fn("play", move |name: &str| p(name, 1.0, 1.0, 0));
+    let p = Arc::clone(&play);
+    engine.register_fn("play", move |name: &str, volume: f64| {
+        p(name, volume, 1.0, 0)
+    });
+    let p = Arc::clone(&play);
+    engine.register_fn("play", move |name: &str, volume: f64, pitch: f64| {
+        p(name, volume, pitch, 0)
+    });
+    let p = Arc::clone(&play);
+    engine.register_fn("schedule", move |delay_ms: i64, name: &str| {
+        p(name, 1.0, 1.0, delay_ms)
+    });
+    engine.register_fn(
+        "schedule",
+        move |delay_ms: i64, name: &str, volume: f64, pitch: f64| {
+            play(name, volume, pitch, delay_ms)
+        },
+    );
+    engine
+}
+
+/// Run the script handlers of a view, and play the sounds they request
+pub struct ScriptSink {
+    script: Arc<Script>,
+    audio: Arc<AudioFiles>,
+    output: Arc<dyn AudioSink>,
+    volume: f32,
+}
+
+impl ScriptSink {
+    pub fn new(
+        script: Arc<Script>,
+        audio: Arc<AudioFiles>,
+        output: Arc<dyn AudioSink>,
+        volume: f32,
+    ) -> Self {
+        Self {
+            script,
+            audio,
+            output,
+            volume,
+        }
+    }
+
+    fn play(&self, event: EventKind, request: PlayRequest) {
+        // the event sounds use the view overrides
+        let sound = match EventKind::from_name(&request.name) {
+            Some(kind) => self.audio.get(kind).cloned(),
+            None => self.script.sound(&request.name),
+        };
+        let Some(sound) = sound else {
+            warn!(&format!("No sound {} for the script", request.name));
+            return;
+        };
+        let mut params = sound.params;
+        params.volume = Some(params.volume.unwrap_or(1.0) * request.volume);
+        params.speed = Some(params.speed.unwrap_or(1.0) * request.pitch);
+        if request.delay_ms > 0 {
+            params.delay_ms =
+                Some(params.delay_ms.unwrap_or(0) + request.delay_ms);
+        }
+        if let Err(e) = params.validate() {
+            err!(&format!("Invalid play from the script: {e}"));
+            return;
+        }
+        let sound =
+            PendingSound::with_params(event, sound, params, self.volume);
+        self.output.play(sound);
+    }
+}
+
+impl EventSink for ScriptSink {
+    fn interest(&self, kind: EventKind) -> Interest {
+        if self.script.handles(kind) {
+            Interest::Args
+        } else {
+            Interest::None
+        }
+    }
+
+    fn handle(&self, event: &Event) {
+        for request in self.script.run(event) {
+            self.play(event.kind(), request);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::event::EventArgs;
+
+    fn script(source: &str) -> Script {
+        Script::new(source, PathBuf::new(), CacheConfig::default()).unwrap()
+    }
+
+    fn event(kind: EventKind, name: &str) -> Event {
+        let args = EventArgs {
+            name: Some(name.to_string()),
+            len: Some(4),
+            ..EventArgs::default()
+        };
+        Event::with_args(kind, args)
+    }
+
+    fn request(
+        name: &str,
+        volume: f32,
+        pitch: f32,
+        delay_ms: u64,
+    ) -> PlayRequest {
+        PlayRequest {
+            name: name.to_string(),
+            volume,
+            pitch,
+            delay_ms,
+        }
+    }
+
+    #[test]
+    fn handlers_get_the_args() {
+        let script = script(
+            r#"
+            fn symbol_added(args) {
+                if args.name.starts_with("mem") {
+                    play("tag_added", 0.5, args.len / 2.0);
+                }
+            }
+            "#,
+        );
+        assert!(script.handles(EventKind::SymbolAdded));
+        assert!(!script.handles(EventKind::SymbolRemoved));
+        let played = script.run(&event(EventKind::SymbolAdded, "memcpy"));
+        assert_eq!(played, [request("tag_added", 0.5, 2.0, 0)]);
+        assert!(
+            script
+                .run(&event(EventKind::SymbolAdded, "strcpy"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn state_between_calls() {
+        let script = script(
+            r#"
+            fn init() {
+                this.count = 0;
+            }
+            fn function_added(args) {
+                this.count += 1;
+                if this.count % 3 == 0 {
+                    schedule(100, "builtin:add.wav");
+                }
+            }
+            "#,
+        );
+        let played: Vec<_> = (0..6)
+            .map(|_| script.run(&event(EventKind::FunctionAdded, "f")).len())
+            .collect();
+        assert_eq!(played, [0, 0, 1, 0, 0, 1]);
+    }
+
+    #[test]
+    fn errors() {
+        let new = |source| {
+            Script::new(source, PathBuf::new(), CacheConfig::default())
+        };
+        assert!(new("fn function_added(args) {").is_err());
+        assert!(new("fn function_added() {}").is_err());
+        assert!(new("fn init() { undefined() }").is_err());
+        // a runtime error is logged, the script keeps working
+        let script = script(
+            r#"
+            fn tag_added(args) {
+                if args.len == 4 { throw "error" }
+                play("tag_added");
+            }
+            fn tag_removed(args) { loop {} }
+            "#,
+        );
+        let events = |kind| script.run(&event(kind, "t"));
+        assert!(events(EventKind::TagAdded).is_empty());
+        assert!(events(EventKind::TagRemoved).is_empty());
+    }
+
+    struct View;
+
+    impl crate::event::ViewInfo for View {
+        fn sections_at(&self, address: u64) -> Vec<String> {
+            match address {
+                0x1000..0x2000 => vec![".text".to_string()],
+                _ => vec![],
+            }
+        }
+
+        fn permissions_at(&self, _address: u64) -> Option<u8> {
+            None
+        }
+
+        fn address_range(&self) -> std::ops::Range<u64> {
+            0..0x3000
+        }
+    }
+
+    #[test]
+    fn sections_and_time() {
+        // the README example
+        let script = script(
+            r#"
+            fn init() {
+                this.recent = [];
+            }
+            fn function_added(args) {
+                if !args.sections.contains(".text") {
+                    return;
+                }
+                this.recent.push(timestamp());
+                this.recent.retain(|time| time.elapsed < 1.0);
+                if this.recent.len() == 3 {
+                    play("tag_added", 0.8, 1.5);
+                    schedule(250, "builtin:add.wav", 0.8, 2.0);
+                }
+            }
+            "#,
+        );
+        let run = |address| {
+            let args = EventArgs {
+                view: Some(Arc::new(View)),
+                address: Some(address),
+                ..EventArgs::default()
+            };
+            script.run(&Event::with_args(EventKind::FunctionAdded, args))
+        };
+        assert!(run(0x1000).is_empty());
+        assert!(run(0x2800).is_empty());
+        assert!(run(0x1100).is_empty());
+        assert_eq!(
+            run(0x1200),
+            [
+                request("tag_added", 0.8, 1.5, 0),
+                request("builtin:add.wav", 0.8, 2.0, 250)
+            ]
+        );
+    }
+
+    #[test]
+    fn sounds_by_name() {
+        let script = script("");
+        assert!(script.sound("builtin:add.wav").is_some());
+        assert!(script.sound("missing.wav").is_none());
+        for i in 0..MAX_SOUNDS + 10 {
+            assert!(script.sound(&format!("missing{i}.wav")).is_none());
+        }
+        assert_eq!(script.sounds.lock().unwrap().1.len(), MAX_SOUNDS);
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use crate::event::{Event, EventKind};
+
+/// How much of an event a sink needs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Interest {
+    /// the event is not sent to the sink
+    None,
+    /// the event is sent, but it may not be decoded
+    Kind,
+    /// the event is sent decoded, with the args
+    Args,
+}
+
+/// Receive the events, eg: to play a sound or write a trace
+pub trait EventSink: Send + Sync {
+    fn interest(&self, kind: EventKind) -> Interest;
+
+    fn handle(&self, event: &Event);
+}
+
+/// All the sinks receiving the events of a view
+#[derive(Clone, Default)]
+pub struct EventSinks {
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl EventSinks {
+    pub fn register(&mut self, sink: Arc<dyn EventSink>) {
+        self.sinks.push(sink);
+    }
+
+    /// The most any sink needs from the event
+    pub fn interest(&self, kind: EventKind) -> Interest {
+        self.sinks
+            .iter()
+            .map(|sink| sink.interest(kind))
+            .max()
+            .unwrap_or(Interest::None)
+    }
+
+    /// Send the event to the sinks interested in it. The event is only
+    /// decoded if a sink needs the args.
+    pub fn dispatch(&self, kind: EventKind, event: impl FnOnce() -> Event) {
+        let event = match self.interest(kind) {
+            Interest::None => return,
+            Interest::Kind => Event::without_args(kind),
+            Interest::Args => event(),
+        };
+        for sink in &self.sinks {
+            if sink.interest(kind) != Interest::None {
+                sink.handle(&event);
+            }
+        }
+    }
+}
+
+/// Drop the events while muted
+pub struct Mutable {
+    sink: Arc<dyn EventSink>,
+    muted: Box<dyn Fn() -> bool + Send + Sync>,
+}
+
+impl Mutable {
+    pub fn new(
+        sink: Arc<dyn EventSink>,
+        muted: impl Fn() -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            sink,
+            muted: Box::new(muted),
+        }
+    }
+}
+
+impl EventSink for Mutable {
+    fn interest(&self, kind: EventKind) -> Interest {
+        self.sink.interest(kind)
+    }
+
+    fn handle(&self, event: &Event) {
+        if !(self.muted)() {
+            self.sink.handle(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::event::{BinJuiceEvent, FunctionInfo};
+
+    struct Collect {
+        interest: Interest,
+        events: Mutex<Vec<(EventKind, Option<u64>)>>,
+    }
+
+    impl Collect {
+        fn new(interest: Interest) -> Arc<Self> {
+            Arc::new(Self {
+                interest,
+                events: Mutex::default(),
+            })
+        }
+    }
+
+    impl EventSink for Collect {
+        fn interest(&self, kind: EventKind) -> Interest {
+            match kind {
+                EventKind::FunctionAdded => self.interest,
+                _ => Interest::None,
+            }
+        }
+
+        fn handle(&self, event: &Event) {
+            let address = event.args().address;
+            self.events.lock().unwrap().push((event.kind(), address));
+        }
+    }
+
+    fn function_added() -> Event {
+        let function = FunctionInfo {
+            address: 1,
+            name: "main".to_string(),
+            size: 16,
+            basic_blocks: 2,
+        };
+        Event::new(BinJuiceEvent::FunctionAdded(function), None)
+    }
+
+    #[test]
+    fn args_only_decoded_if_needed() {
+        let sink = Collect::new(Interest::Kind);
+        let mut sinks = EventSinks::default();
+        sinks.register(sink.clone());
+        sinks.dispatch(EventKind::FunctionAdded, || {
+            unreachable!("no sink needs the args")
+        });
+        sinks.dispatch(EventKind::TagAdded, || unreachable!("not wanted"));
+        let events = sink.events.lock().unwrap();
+        assert_eq!(*events, [(EventKind::FunctionAdded, None)]);
+    }
+
+    #[test]
+    fn all_interested_sinks_receive_the_event() {
+        let kind = Collect::new(Interest::Kind);
+        let args_sink = Collect::new(Interest::Args);
+        let ignored = Collect::new(Interest::None);
+        let mut sinks = EventSinks::default();
+        sinks.register(kind.clone());
+        sinks.register(args_sink.clone());
+        sinks.register(ignored.clone());
+        assert_eq!(sinks.interest(EventKind::FunctionAdded), Interest::Args);
+        assert_eq!(sinks.interest(EventKind::TagAdded), Interest::None);
+        sinks.dispatch(EventKind::FunctionAdded, function_added);
+        let expected = [(EventKind::FunctionAdded, Some(1))];
+        assert_eq!(*kind.events.lock().unwrap(), expected);
+        assert_eq!(*args_sink.events.lock().unwrap(), expected);
+        assert!(ignored.events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn mutable_drops_while_muted() {
+        let sink = Collect::new(Interest::Args);
+        let muted = Arc::new(AtomicBool::new(false));
+        let mutable = Mutable::new(sink.clone(), {
+            let muted = Arc::clone(&muted);
+            move || muted.load(Ordering::Relaxed)
+        });
+        let event = function_added();
+        mutable.handle(&event);
+        muted.store(true, Ordering::Relaxed);
+        mutable.handle(&event);
+        // still interested, so the notifications stay registered
+        assert_eq!(mutable.interest(EventKind::FunctionAdded), Interest::Args);
+        assert_eq!(sink.events.lock().unwrap().len(), 1);
+    }
+}
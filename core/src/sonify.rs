@@ -0,0 +1,266 @@
+use anyhow::{Result, bail};
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::EventArgs;
+use crate::playback::PlaybackParams;
+
+/// The event argument a mapping reads
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapSource {
+    Address,
+    Len,
+    FunctionSize,
+    BasicBlocks,
+    /// segment permissions, read = 4, write = 2 and execute = 1
+    Permissions,
+}
+
+/// The playback parameter a mapping sets
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapTarget {
+    Volume,
+    Speed,
+    Pan,
+    MaxDurationMs,
+    LowPassHz,
+}
+
+/// Map an event argument into a playback parameter, the input range is
+/// linearly mapped into the output range
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParamMapping {
+    pub from: MapSource,
+    pub to: MapTarget,
+    /// values outside the range are clamped, for the address the default is
+    /// the view address range
+    pub input: Option<[f64; 2]>,
+    pub output: [f64; 2],
+    /// use a logarithmic scale for the input, useful for sizes
+    #[serde(default)]
+    pub log: bool,
+}
+
+impl ParamMapping {
+    fn arg(&self) -> ArgMapping {
+        ArgMapping {
+            from: self.from,
+            input: self.input,
+            output: self.output,
+            log: self.log,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        self.arg().validate()?;
+        // the output is always inside the range, so checking the limits is
+        // enough
+        for output in self.output {
+            let mut params = PlaybackParams::default();
+            self.set(&mut params, output);
+            params.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Events without the source argument are left unchanged
+    pub fn apply(&self, args: &EventArgs, params: &mut PlaybackParams) {
+        if let Some(value) = self.arg().map(args) {
+            self.set(params, value);
+        }
+    }
+
+    fn set(&self, params: &mut PlaybackParams, value: f64) {
+        match self.to {
+            MapTarget::Volume => params.volume = Some(value as f32),
+            MapTarget::Speed => params.speed = Some(value as f32),
+            MapTarget::Pan => params.pan = Some(value as f32),
+            MapTarget::MaxDurationMs => {
+                params.max_duration_ms = Some(value.round() as u64)
+            }
+            MapTarget::LowPassHz => {
+                params.low_pass_hz = Some(value.round() as u32)
+            }
+        }
+    }
+}
+
+/// Map an event argument into a number, the same as [ParamMapping] without
+/// the target, eg: for a MIDI note
+#[derive(Clone, Copy, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArgMapping {
+    pub from: MapSource,
+    pub input: Option<[f64; 2]>,
+    pub output: [f64; 2],
+    #[serde(default)]
+    pub log: bool,
+}
+
+impl ArgMapping {
+    /// Only check the input, the valid output depends on the target
+    pub fn validate(&self) -> Result<()> {
+        match self.input {
+            None if self.from != MapSource::Address => {
+                bail!("Missing the input range")
+            }
+            Some([start, end])
+                if start == end || !(start + end).is_finite() =>
+            {
+                bail!("Invalid input range {start}..{end}")
+            }
+            Some([start, end]) if self.log && start.min(end) < 0.0 => {
+                bail!("The log scale can't be used with negative numbers")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// None if the event doesn't have the source argument
+    pub fn map(&self, args: &EventArgs) -> Option<f64> {
+        let value = self.source(args)?;
+        let [start, end] = self.input.or_else(|| view_range(args))?;
+        let scale = |x: f64| if self.log { x.ln_1p() } else { x };
+        let (value, start, end) = (scale(value), scale(start), scale(end));
+        // an empty view has no range, use the middle of the output
+        let position = if start == end {
+            0.5
+        } else {
+            ((value - start) / (end - start)).clamp(0.0, 1.0)
+        };
+        let [out_start, out_end] = self.output;
+        Some(out_start + (out_end - out_start) * position)
+    }
+
+    fn source(&self, args: &EventArgs) -> Option<f64> {
+        let value = match self.from {
+            MapSource::Address => args.address?,
+            MapSource::Len => args.len?,
+            MapSource::FunctionSize => args.function_size?,
+            MapSource::BasicBlocks => args.basic_blocks?,
+            MapSource::Permissions => permissions(args)?.into(),
+        };
+        Some(value as f64)
+    }
+}
+
+// segment events carry the permissions, otherwise use the segment at the
+// event address
+fn permissions(args: &EventArgs) -> Option<u8> {
+    if let Some(permissions) = args.permissions {
+        return Some(permissions);
+    }
+    let (view, address) = (args.view.as_ref()?, args.address?);
+    view.permissions_at(address)
+}
+
+fn view_range(args: &EventArgs) -> Option<[f64; 2]> {
+    let view = args.view.as_ref()?;
+    let range = view.address_range();
+    Some([range.start as f64, range.end as f64])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(yaml: &str) -> ParamMapping {
+        let mapping: ParamMapping = serde_yaml::from_str(yaml).unwrap();
+        mapping.validate().unwrap();
+        mapping
+    }
+
+    fn apply(mapping: &ParamMapping, args: EventArgs) -> PlaybackParams {
+        let mut params = PlaybackParams::default();
+        mapping.apply(&args, &mut params);
+        params
+    }
+
+    fn len(len: u64) -> EventArgs {
+        EventArgs {
+            len: Some(len),
+            ..EventArgs::default()
+        }
+    }
+
+    #[test]
+    fn linear_and_clamped() {
+        let mapping =
+            mapping("{from: len, to: pan, input: [0, 100], output: [-1, 1]}");
+        assert_eq!(apply(&mapping, len(25)).pan, Some(-0.5));
+        assert_eq!(apply(&mapping, len(1000)).pan, Some(1.0));
+    }
+
+    #[test]
+    fn log_scale() {
+        let mapping = mapping(
+            "{from: len, to: speed, input: [0, 99], output: [1, 2], log: true}",
+        );
+        // ln(1 + 9) is half of ln(1 + 99)
+        let speed = apply(&mapping, len(9)).speed.unwrap();
+        assert!((speed - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn missing_argument_is_unchanged() {
+        let mapping = mapping(
+            "{from: function_size, to: volume, input: [0, 1], output: [0, 1]}",
+        );
+        assert_eq!(apply(&mapping, len(1)).volume, None);
+    }
+
+    #[test]
+    fn address_needs_the_view_range() {
+        let mapping = mapping("{from: address, to: pan, output: [-1, 1]}");
+        let args = EventArgs {
+            address: Some(0x1000),
+            ..EventArgs::default()
+        };
+        assert_eq!(apply(&mapping, args).pan, None);
+    }
+
+    #[test]
+    fn empty_view_range() {
+        struct EmptyView;
+        impl crate::event::ViewInfo for EmptyView {
+            fn sections_at(&self, _address: u64) -> Vec<String> {
+                vec![]
+            }
+            fn permissions_at(&self, _address: u64) -> Option<u8> {
+                None
+            }
+            fn address_range(&self) -> std::ops::Range<u64> {
+                0x1000..0x1000
+            }
+        }
+
+        let mapping = mapping("{from: address, to: pan, output: [-1, 1]}");
+        let args = EventArgs {
+            view: Some(std::sync::Arc::new(EmptyView)),
+            address: Some(0x1000),
+            ..EventArgs::default()
+        };
+        assert_eq!(apply(&mapping, args).pan, Some(0.0));
+    }
+
+    #[test]
+    fn invalid_mappings() {
+        let invalid = |yaml| {
+            let mapping: ParamMapping = serde_yaml::from_str(yaml).unwrap();
+            mapping.validate().is_err()
+        };
+        assert!(invalid("{from: len, to: pan, output: [-1, 1]}"));
+        assert!(invalid(
+            "{from: len, to: pan, input: [1, 1], output: [0, 1]}"
+        ));
+        assert!(invalid(
+            "{from: len, to: pan, input: [0, 1], output: [0, 2]}"
+        ));
+        assert!(invalid(
+            "{from: len, to: speed, input: [-1, 1], output: [1, 2], log: true}"
+        ));
+    }
+}
@@ -0,0 +1,390 @@
+use std::f32::consts::TAU;
+
+use anyhow::{Result, bail};
+
+use rodio::buffer::SamplesBuffer;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{EventKind, EventKind as E};
+use crate::playback::{DetailedSoundConfig, PlaybackParams, SoundConfig};
+use crate::scheduler::ScheduleParams;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wave {
+    #[default]
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+    Noise,
+}
+
+impl Wave {
+    // phase goes from 0.0 to 1.0
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Wave::Sine => (phase * TAU).sin(),
+            Wave::Square if phase < 0.5 => 1.0,
+            Wave::Square => -1.0,
+            Wave::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Wave::Sawtooth => 2.0 * phase - 1.0,
+            Wave::Noise => fastrand::f32() * 2.0 - 1.0,
+        }
+    }
+}
+
+/// One frequency, or multiple played together as a chord
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Frequency {
+    Note(f32),
+    Chord(Vec<f32>),
+}
+
+impl Default for Frequency {
+    fn default() -> Self {
+        Frequency::Note(440.0)
+    }
+}
+
+impl Frequency {
+    fn notes(&self) -> &[f32] {
+        match self {
+            Frequency::Note(freq) => std::slice::from_ref(freq),
+            Frequency::Chord(freqs) => freqs,
+        }
+    }
+}
+
+/// A sound generated at load time, instead of read from a file
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SynthConfig {
+    #[serde(default)]
+    pub wave: Wave,
+    /// in Hz, ignored by the noise
+    #[serde(default)]
+    pub freq: Frequency,
+    /// fade in time
+    #[serde(default = "default_attack_ms")]
+    pub attack_ms: u64,
+    /// time at full volume, between the attack and the release
+    #[serde(default = "default_hold_ms")]
+    pub hold_ms: u64,
+    /// fade out time
+    #[serde(default = "default_release_ms")]
+    pub release_ms: u64,
+    /// the frequency is multiplied by this at the end of the sound, eg: 2.0
+    /// glides one octave up
+    pub sweep: Option<f32>,
+}
+
+// the samples are rendered at load time, so keep them short
+const MAX_DURATION_MS: u64 = 10_000;
+
+fn default_attack_ms() -> u64 {
+    5
+}
+
+fn default_hold_ms() -> u64 {
+    50
+}
+
+fn default_release_ms() -> u64 {
+    80
+}
+
+impl SynthConfig {
+    pub fn validate(&self) -> Result<()> {
+        let notes = self.freq.notes();
+        if notes.is_empty() {
+            bail!("The chord has no frequencies");
+        }
+        let nyquist = SAMPLE_RATE as f32 / 2.0;
+        for &freq in notes {
+            if !(freq > 0.0 && freq < nyquist) {
+                bail!("Invalid frequency {freq}, the range is 0..{nyquist} Hz");
+            }
+        }
+        if let Some(sweep) = self.sweep
+            && !(sweep > 0.0 && sweep.is_finite())
+        {
+            bail!("Invalid sweep {sweep}, it needs to be bigger than zero");
+        }
+        let duration = [self.attack_ms, self.hold_ms, self.release_ms]
+            .into_iter()
+            .fold(0u64, u64::saturating_add);
+        if duration == 0 {
+            bail!("The synth sound can't be empty");
+        }
+        if duration > MAX_DURATION_MS {
+            bail!(
+                "The synth sound lasts {duration} ms, the maximum is \
+                {MAX_DURATION_MS} ms"
+            );
+        }
+        Ok(())
+    }
+
+    /// Generate the samples, the sounds are short so they are kept in memory
+    pub fn render(&self) -> SamplesBuffer {
+        let to_samples =
+            |ms: u64| (ms * u64::from(SAMPLE_RATE) / 1000) as usize;
+        let attack = to_samples(self.attack_ms);
+        let hold = to_samples(self.hold_ms);
+        let release = to_samples(self.release_ms);
+        let len = attack + hold + release;
+
+        let notes = self.freq.notes();
+        let sweep = self.sweep.unwrap_or(1.0);
+        // leave some headroom, so multiple sounds can be mixed
+        let gain = 0.5 / notes.len() as f32;
+        let mut phases = vec![0.0f32; notes.len()];
+        let mut samples = Vec::with_capacity(len);
+        for i in 0..len {
+            let progress = i as f32 / len as f32;
+            let envelope = if i < attack {
+                i as f32 / attack as f32
+            } else if i < attack + hold {
+                1.0
+            } else {
+                (len - i) as f32 / release as f32
+            };
+            let glide = sweep.powf(progress);
+            let mut sample = 0.0;
+            for (phase, freq) in phases.iter_mut().zip(notes) {
+                sample += self.wave.sample(*phase);
+                *phase = (*phase + freq * glide / SAMPLE_RATE as f32).fract();
+            }
+            samples.push(sample * gain * envelope);
+        }
+        SamplesBuffer::new(1, SAMPLE_RATE, samples)
+    }
+}
+
+/// The built-in sound for the event, so BinJuice works without any files.
+/// The object kind selects the pitch and the action selects the timbre.
+pub fn preset(event: EventKind) -> SoundConfig {
+    let freq = pitch(event);
+    let note = |wave, sweep, hold_ms| SynthConfig {
+        wave,
+        freq: Frequency::Note(freq),
+        attack_ms: default_attack_ms(),
+        hold_ms,
+        release_ms: default_release_ms(),
+        sweep,
+    };
+    let chord = |freq: &[f32], hold_ms, sweep| SynthConfig {
+        wave: Wave::Triangle,
+        freq: Frequency::Chord(freq.to_vec()),
+        attack_ms: 20,
+        hold_ms,
+        release_ms: 400,
+        sweep,
+    };
+
+    let (synth, volume) = match event {
+        E::StartBinaryNinja => {
+            (chord(&[261.63, 329.63, 392.00], 300, None), 1.0)
+        }
+        E::EndBinaryNinja => {
+            (chord(&[392.00, 329.63, 261.63], 300, Some(0.5)), 1.0)
+        }
+        E::StartBinaryView => {
+            (chord(&[392.00, 493.88, 587.33], 150, None), 1.0)
+        }
+        E::EndBinaryView => {
+            (chord(&[392.00, 493.88, 587.33], 150, Some(0.5)), 1.0)
+        }
+        // sent all the time, just a faint tick
+        E::NotificationBarrier => (
+            SynthConfig {
+                wave: Wave::Noise,
+                freq: Frequency::default(),
+                attack_ms: 1,
+                hold_ms: 5,
+                release_ms: 10,
+                sweep: None,
+            },
+            0.1,
+        ),
+        E::UndoEntryAdded | E::UndoEntryTaken | E::RedoEntryTaken => {
+            (note(Wave::Sawtooth, None, 20), 0.4)
+        }
+        E::Rebased => (note(Wave::Sawtooth, Some(2.0), 200), 0.6),
+        // added, inserted, defined, found, attached, connected
+        E::DataInserted
+        | E::FunctionAdded
+        | E::DataVariableAdded
+        | E::TagAdded
+        | E::SymbolAdded
+        | E::StringFound
+        | E::TypeDefined
+        | E::SegmentAdded
+        | E::SectionAdded
+        | E::ComponentAdded
+        | E::ComponentFunctionAdded
+        | E::ComponentDataVariableAdded
+        | E::ExternalLibraryAdded
+        | E::ExternalLocationAdded
+        | E::TypeArchiveAttached
+        | E::TypeArchiveConnected => (note(Wave::Sine, Some(1.5), 40), 0.6),
+        // removed, undefined, detached, disconnected
+        E::DataRemoved
+        | E::FunctionRemoved
+        | E::DataVariableRemoved
+        | E::TagRemoved
+        | E::SymbolRemoved
+        | E::StringRemoved
+        | E::TypeUndefined
+        | E::SegmentRemoved
+        | E::SectionRemoved
+        | E::ComponentRemoved
+        | E::ComponentFunctionRemoved
+        | E::ComponentDataVariableRemoved
+        | E::ExternalLibraryRemoved
+        | E::ExternalLocationRemoved
+        | E::TypeArchiveDetached
+        | E::TypeArchiveDisconnected => {
+            (note(Wave::Square, Some(0.5), 40), 0.3)
+        }
+        // updated, changed, moved, written, requested
+        E::DataWritten
+        | E::FunctionUpdated
+        | E::FunctionUpdateRequested
+        | E::DataVariableUpdated
+        | E::DataMetadataUpdated
+        | E::TagTypeUpdated
+        | E::TagUpdated
+        | E::SymbolUpdated
+        | E::TypeReferenceChanged
+        | E::TypeFieldReferenceChanged
+        | E::SegmentUpdated
+        | E::SectionUpdated
+        | E::ComponentNameUpdated
+        | E::ComponentMoved
+        | E::ExternalLibraryUpdated
+        | E::ExternalLocationUpdated => (note(Wave::Triangle, None, 20), 0.4),
+    };
+
+    SoundConfig::Detailed(Box::new(DetailedSoundConfig {
+        synth: Some(synth),
+        params: PlaybackParams {
+            volume: Some(volume),
+            ..PlaybackParams::default()
+        },
+        // the analysis can send thousands of events, don't play all of them
+        schedule: ScheduleParams {
+            cooldown_ms: Some(50),
+            max_voices: Some(8),
+            coalesce: None,
+        },
+        ..DetailedSoundConfig::default()
+    }))
+}
+
+// the note of the events about the same kind of object, in the C major
+// scale, so the events sound fine together
+fn pitch(event: EventKind) -> f32 {
+    match event {
+        E::TypeArchiveAttached
+        | E::TypeArchiveDetached
+        | E::TypeArchiveConnected
+        | E::TypeArchiveDisconnected => 261.63,
+        E::ExternalLibraryAdded
+        | E::ExternalLibraryUpdated
+        | E::ExternalLibraryRemoved
+        | E::ExternalLocationAdded
+        | E::ExternalLocationUpdated
+        | E::ExternalLocationRemoved => 293.66,
+        E::ComponentNameUpdated
+        | E::ComponentAdded
+        | E::ComponentMoved
+        | E::ComponentRemoved
+        | E::ComponentFunctionAdded
+        | E::ComponentFunctionRemoved
+        | E::ComponentDataVariableAdded
+        | E::ComponentDataVariableRemoved => 329.63,
+        E::SegmentAdded | E::SegmentRemoved | E::SegmentUpdated => 349.23,
+        E::SectionAdded | E::SectionRemoved | E::SectionUpdated => 392.00,
+        E::TypeDefined
+        | E::TypeUndefined
+        | E::TypeReferenceChanged
+        | E::TypeFieldReferenceChanged => 440.00,
+        E::DataWritten
+        | E::DataInserted
+        | E::DataRemoved
+        | E::DataVariableAdded
+        | E::DataVariableRemoved
+        | E::DataVariableUpdated
+        | E::DataMetadataUpdated => 493.88,
+        E::FunctionAdded
+        | E::FunctionRemoved
+        | E::FunctionUpdated
+        | E::FunctionUpdateRequested => 523.25,
+        E::SymbolAdded | E::SymbolRemoved | E::SymbolUpdated => 587.33,
+        E::StringFound | E::StringRemoved => 659.25,
+        E::TagTypeUpdated | E::TagAdded | E::TagRemoved | E::TagUpdated => {
+            698.46
+        }
+        E::StartBinaryNinja
+        | E::EndBinaryNinja
+        | E::StartBinaryView
+        | E::EndBinaryView
+        | E::NotificationBarrier
+        | E::UndoEntryAdded
+        | E::UndoEntryTaken
+        | E::RedoEntryTaken
+        | E::Rebased => 783.99,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::Source;
+
+    use super::*;
+
+    fn synth(yaml: &str) -> SynthConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn render_length_and_envelope() {
+        let config = synth("{attack_ms: 10, hold_ms: 20, release_ms: 70}");
+        config.validate().unwrap();
+        let sound = config.render();
+        assert_eq!(sound.channels(), 1);
+        let samples: Vec<f32> = sound.collect();
+        assert_eq!(samples.len(), 4410);
+        // starts silent and never clips
+        assert_eq!(samples[0], 0.0);
+        assert!(samples.iter().all(|sample| sample.abs() <= 0.5));
+    }
+
+    #[test]
+    fn invalid_synths() {
+        assert!(synth("freq: []").validate().is_err());
+        assert!(synth("freq: 30000").validate().is_err());
+        assert!(synth("sweep: 0").validate().is_err());
+        let empty = "{attack_ms: 0, hold_ms: 0, release_ms: 0}";
+        assert!(synth(empty).validate().is_err());
+        assert!(synth("hold_ms: 20000").validate().is_err());
+        let huge = format!("release_ms: {}", u64::MAX);
+        assert!(synth(&huge).validate().is_err());
+    }
+
+    #[test]
+    fn preset_for_all_events() {
+        for &event in EventKind::ALL {
+            let SoundConfig::Detailed(config) = preset(event) else {
+                panic!("the preset is always detailed");
+            };
+            config.synth.as_ref().unwrap().validate().unwrap();
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, EventArgs, EventKind, ViewInfo};
+use crate::sink::{EventSink, Interest};
+
+/// One line of the trace file
+#[derive(Deserialize, Serialize)]
+pub struct TraceEvent {
+    /// seconds since the unix epoch
+    pub time: f64,
+    pub event: EventKind,
+    pub view: Option<TraceView>,
+    /// missing at the traces of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_info: Option<TraceViewInfo>,
+    pub args: EventArgs,
+}
+
+impl TraceEvent {
+    /// The args with the view info, so the filters and mappings that use
+    /// the view work as when traced
+    pub fn into_args(self) -> EventArgs {
+        EventArgs {
+            view: self
+                .view_info
+                .map(|info| Arc::new(info) as Arc<dyn ViewInfo>),
+            ..self.args
+        }
+    }
+}
+
+/// Identify the view that received the event
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TraceView {
+    pub session: usize,
+    pub file: String,
+}
+
+/// What the view knew about the event, when it was traced
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TraceViewInfo {
+    pub range: Range<u64>,
+    /// the sections containing the event address
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<String>,
+    /// of the segment containing the event address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub permissions: Option<u8>,
+}
+
+impl TraceViewInfo {
+    fn new(view: &dyn ViewInfo, address: Option<u64>) -> Self {
+        Self {
+            range: view.address_range(),
+            sections: address
+                .map(|address| view.sections_at(address))
+                .unwrap_or_default(),
+            permissions: address
+                .and_then(|address| view.permissions_at(address)),
+        }
+    }
+}
+
+// only the event address is ever looked up
+impl ViewInfo for TraceViewInfo {
+    fn sections_at(&self, _address: u64) -> Vec<String> {
+        self.sections.clone()
+    }
+
+    fn permissions_at(&self, _address: u64) -> Option<u8> {
+        self.permissions
+    }
+
+    fn address_range(&self) -> Range<u64> {
+        self.range.clone()
+    }
+}
+
+/// Append every event received to a JSONL file
+pub struct Tracer {
+    file: Mutex<LineWriter<File>>,
+}
+
+impl Tracer {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Unable to open {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(LineWriter::new(file)),
+        })
+    }
+
+    pub fn write(
+        &self,
+        event: EventKind,
+        view: Option<&TraceView>,
+        args: &EventArgs,
+    ) {
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let event = TraceEvent {
+            time,
+            event,
+            view: view.cloned(),
+            view_info: args
+                .view
+                .as_deref()
+                .map(|info| TraceViewInfo::new(info, args.address)),
+            args: args.clone(),
+        };
+        // only plain data, so it can't fail
+        let line = serde_json::to_string(&event).unwrap();
+        if let Err(e) = writeln!(self.file.lock().unwrap(), "{line}") {
+            err!(&format!("Unable to write the trace: {e}"));
+        }
+    }
+}
+
+/// Trace all the events of a view
+pub struct TraceSink {
+    tracer: Arc<Tracer>,
+    view: Option<TraceView>,
+}
+
+impl TraceSink {
+    pub fn new(tracer: Arc<Tracer>, view: Option<TraceView>) -> Self {
+        Self { tracer, view }
+    }
+}
+
+impl EventSink for TraceSink {
+    fn interest(&self, _kind: EventKind) -> Interest {
+        Interest::Args
+    }
+
+    fn handle(&self, event: &Event) {
+        self.tracer
+            .write(event.kind(), self.view.as_ref(), event.args());
+    }
+}
@@ -0,0 +1,269 @@
+use std::sync::Arc;
+
+use binaryninja::binary_view::BinaryView;
+use binaryninja::command::{Command, register_command};
+use binaryninja::interaction::{get_choice_input, get_save_filename_input};
+
+use binjuice_core::assets;
+use binjuice_core::event::EventKind;
+use binjuice_core::output;
+use binjuice_core::profile::DEFAULT_PROFILE;
+
+use crate::{SOUND_HANDLER, SoundContext, SoundHandler, VIEW_REGISTRY};
+
+// the sounds of the view, or the global ones if the view is not registered
+fn view_context(view: &BinaryView) -> Option<Arc<SoundContext>> {
+    let registry = VIEW_REGISTRY.lock().unwrap();
+    match registry.get(view) {
+        Some(registration) => registration.context(view),
+        None => SOUND_HANDLER.get()?.context().map(Arc::new),
+    }
+}
+
+struct WriteDefaultConfig;
+impl Command for WriteDefaultConfig {
+    fn action(&self, _view: &BinaryView) {
+        let path = SoundHandler::config_path();
+        if path.exists() {
+            warn!(&format!("{} already exists", path.display()));
+            return;
+        }
+        match std::fs::write(&path, assets::DEFAULT_CONFIG) {
+            Ok(()) => info!(&format!("Config written to {}", path.display())),
+            Err(e) => err!(&format!("Unable to write the config: {e}")),
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        !SoundHandler::config_path().exists()
+    }
+}
+
+struct ReloadConfig;
+impl Command for ReloadConfig {
+    fn action(&self, _view: &BinaryView) {
+        let Some(sound_handler) = SOUND_HANDLER.get() else {
+            return;
+        };
+        if let Err(e) = sound_handler.reload() {
+            err!(&format!("Unable to reload the config: {e}"));
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER.get().is_some()
+    }
+}
+
+// mute or unmute all the sounds
+struct Mute(bool);
+impl Command for Mute {
+    fn action(&self, _view: &BinaryView) {
+        if let Some(sound_handler) = SOUND_HANDLER.get() {
+            sound_handler.set_muted(self.0);
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER
+            .get()
+            .is_some_and(|sound_handler| sound_handler.is_muted() != self.0)
+    }
+}
+
+// mute or unmute only the current view
+struct MuteView(bool);
+impl Command for MuteView {
+    fn action(&self, view: &BinaryView) {
+        if let Some(registration) = VIEW_REGISTRY.lock().unwrap().get(view) {
+            registration.set_muted(self.0);
+        }
+    }
+
+    fn valid(&self, view: &BinaryView) -> bool {
+        VIEW_REGISTRY
+            .lock()
+            .unwrap()
+            .get(view)
+            .is_some_and(|registration| registration.is_muted() != self.0)
+    }
+}
+
+struct PlayEvent;
+impl Command for PlayEvent {
+    fn action(&self, view: &BinaryView) {
+        let Some(context) = view_context(view) else {
+            warn!("BinJuice is disabled for this view");
+            return;
+        };
+        let events: Vec<&str> =
+            EventKind::ALL.iter().map(|event| event.name()).collect();
+        let Some(event) =
+            get_choice_input("Event", "Play sound for event", &events)
+        else {
+            return;
+        };
+        context.test_play(EventKind::ALL[event]);
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER.get().is_some()
+    }
+}
+
+struct SwitchProfile;
+impl Command for SwitchProfile {
+    fn action(&self, _view: &BinaryView) {
+        let Some(sound_handler) = SOUND_HANDLER.get() else {
+            return;
+        };
+        let mut profiles = vec![DEFAULT_PROFILE.to_string()];
+        profiles.extend(sound_handler.profiles());
+        let choices: Vec<&str> = profiles.iter().map(String::as_str).collect();
+        let Some(profile) =
+            get_choice_input("Profile", "Switch profile", &choices)
+        else {
+            return;
+        };
+        let profile = profiles.swap_remove(profile);
+        match sound_handler.switch_profile(profile.clone()) {
+            Ok(()) => info!(&format!("Switched to profile {profile}")),
+            Err(e) => err!(&format!("Unable to switch profile: {e:#}")),
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER.get().is_some()
+    }
+}
+
+// start or stop recording the session audio
+struct Record(bool);
+impl Command for Record {
+    fn action(&self, _view: &BinaryView) {
+        let Some(sound_handler) = SOUND_HANDLER.get() else {
+            return;
+        };
+        if self.0 {
+            match sound_handler.output.start_recording() {
+                Ok(()) => info!("Recording the session audio"),
+                Err(e) => err!(&format!("Unable to start recording: {e:#}")),
+            }
+            return;
+        }
+        let Some(recorder) = sound_handler.output.stop_recording() else {
+            return;
+        };
+        let Some(path) =
+            get_save_filename_input("Save recording", "wav", "binjuice.wav")
+        else {
+            return;
+        };
+        match recorder.save_as(&path) {
+            Ok(()) => info!(&format!("Recording saved to {}", path.display())),
+            Err(e) => err!(&format!("Unable to save the recording: {e:#}")),
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER.get().is_some_and(|sound_handler| {
+            sound_handler.output.is_recording() != self.0
+        })
+    }
+}
+
+struct ListDevices;
+impl Command for ListDevices {
+    fn action(&self, _view: &BinaryView) {
+        match output::device_names() {
+            Ok(names) => {
+                info!(&format!("Audio devices: {}", names.join(", ")))
+            }
+            Err(e) => err!(&format!("{e:#}")),
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        true
+    }
+}
+
+struct ListTriggers;
+impl Command for ListTriggers {
+    fn action(&self, view: &BinaryView) {
+        let registry = VIEW_REGISTRY.lock().unwrap();
+        let Some(registration) = registry.get(view) else {
+            info!("BinJuice is not attached to this view yet");
+            return;
+        };
+        let Some(context) = registration.context(view) else {
+            info!("BinJuice is disabled for this view");
+            return;
+        };
+        let triggers = context.active_triggers();
+        if triggers.is_empty() {
+            info!("No active triggers for this view");
+        } else {
+            info!(&format!("Active triggers: {}", triggers.join(", ")));
+        }
+    }
+
+    fn valid(&self, _view: &BinaryView) -> bool {
+        SOUND_HANDLER.get().is_some()
+    }
+}
+
+pub fn register_commands() {
+    register_command(
+        "BinJuice\\Reload BinJuice config",
+        "Reload the BinJuice config file",
+        ReloadConfig,
+    );
+    register_command(
+        "BinJuice\\Write default config",
+        "Write the default config to the user directory, to customize it",
+        WriteDefaultConfig,
+    );
+    register_command("BinJuice\\Mute", "Mute all the sounds", Mute(true));
+    register_command("BinJuice\\Unmute", "Unmute all the sounds", Mute(false));
+    register_command(
+        "BinJuice\\Mute this view",
+        "Mute the sounds of the current view",
+        MuteView(true),
+    );
+    register_command(
+        "BinJuice\\Unmute this view",
+        "Unmute the sounds of the current view",
+        MuteView(false),
+    );
+    register_command(
+        "BinJuice\\Play sound for event...",
+        "Play the sound configured for an event",
+        PlayEvent,
+    );
+    register_command(
+        "BinJuice\\Switch profile...",
+        "Select the BinJuice profile, until Binary Ninja is restarted",
+        SwitchProfile,
+    );
+    register_command(
+        "BinJuice\\Start recording session audio",
+        "Mix all the sounds played into a WAV file",
+        Record(true),
+    );
+    register_command(
+        "BinJuice\\Stop recording session audio",
+        "Stop the recording and save the WAV file",
+        Record(false),
+    );
+    register_command(
+        "BinJuice\\List audio devices",
+        "Log the names of the audio devices, to use in the config",
+        ListDevices,
+    );
+    register_command(
+        "BinJuice\\List active triggers",
+        "Log the notifications BinJuice is listening to on the current view",
+        ListTriggers,
+    );
+}
@@ -0,0 +1,116 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use binaryninja::binary_view::{BinaryView, BinaryViewExt, StringType};
+use binaryninja::function::Function;
+use binaryninja::rc::Ref;
+use binaryninja::section::Section;
+use binaryninja::segment::Segment;
+use binaryninja::symbol::Symbol;
+use binaryninja::tags::TagReference;
+
+use binjuice_core::event::{
+    FunctionInfo, SectionInfo, SegmentInfo, StringInfo, SymbolInfo, TagInfo,
+    ViewInfo,
+};
+use binjuice_core::filter::StringKind;
+use binjuice_core::trace::TraceView;
+
+// the view info used by the filters and mappings
+struct ViewInfoAdapter(Ref<BinaryView>);
+
+impl ViewInfo for ViewInfoAdapter {
+    fn sections_at(&self, address: u64) -> Vec<String> {
+        self.0
+            .sections_at(address)
+            .iter()
+            .map(|section| section.name().to_string())
+            .collect()
+    }
+
+    fn permissions_at(&self, address: u64) -> Option<u8> {
+        self.0
+            .segment_at(address)
+            .map(|segment| segment_permissions(&segment))
+    }
+
+    fn address_range(&self) -> Range<u64> {
+        let start = self.0.start();
+        start..start + self.0.len()
+    }
+}
+
+fn segment_permissions(segment: &Segment) -> u8 {
+    (u8::from(segment.readable()) << 2)
+        | (u8::from(segment.writable()) << 1)
+        | u8::from(segment.executable())
+}
+
+/// Identify the view in the trace
+pub fn trace_view(view: &BinaryView) -> TraceView {
+    let file = view.file();
+    TraceView {
+        session: file.session_id(),
+        file: file.filename().to_string(),
+    }
+}
+
+/// The view info of the filters and mappings, only built if a sink needs the
+/// args
+pub fn view_info(view: &BinaryView) -> Arc<dyn ViewInfo> {
+    Arc::new(ViewInfoAdapter(view.to_owned()))
+}
+
+pub fn function_info(function: &Function) -> FunctionInfo {
+    FunctionInfo {
+        address: function.start(),
+        name: function.symbol().full_name().to_string(),
+        size: function.highest_address() - function.lowest_address(),
+        basic_blocks: function.basic_blocks().len() as u64,
+    }
+}
+
+pub fn tag_info(tag: &TagReference) -> TagInfo {
+    TagInfo {
+        address: tag.addr,
+        tag_type: tag.tag.t().name().to_string(),
+    }
+}
+
+pub fn symbol_info(symbol: &Symbol) -> SymbolInfo {
+    SymbolInfo {
+        address: symbol.address(),
+        name: symbol.full_name().to_string(),
+    }
+}
+
+pub fn string_info(
+    string_type: StringType,
+    offset: u64,
+    len: usize,
+) -> StringInfo {
+    StringInfo {
+        address: offset,
+        len: len as u64,
+        string_type: match string_type {
+            StringType::AsciiString => StringKind::Ascii,
+            StringType::Utf8String => StringKind::Utf8,
+            StringType::Utf16String => StringKind::Utf16,
+            StringType::Utf32String => StringKind::Utf32,
+        },
+    }
+}
+
+pub fn segment_info(segment: &Segment) -> SegmentInfo {
+    SegmentInfo {
+        range: segment.address_range(),
+        permissions: segment_permissions(segment),
+    }
+}
+
+pub fn section_info(section: &Section) -> SectionInfo {
+    SectionInfo {
+        range: section.address_range(),
+        name: section.name().to_string(),
+    }
+}
@@ -0,0 +1,919 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+
+use anyhow::Result;
+
+use paste::paste;
+
+use binaryninja::binary_view::{
+    BinaryView, BinaryViewEventHandler, BinaryViewEventType, StringType,
+};
+use binaryninja::component::Component;
+use binaryninja::data_notification::{
+    CustomDataNotification, DataNotificationTriggers,
+};
+use binaryninja::database::undo::UndoEntry;
+use binaryninja::external_library::{ExternalLibrary, ExternalLocation};
+use binaryninja::function::Function;
+use binaryninja::logger::BnLogLevel;
+use binaryninja::section::Section;
+use binaryninja::segment::Segment;
+use binaryninja::symbol::Symbol;
+use binaryninja::tags::{TagReference, TagType};
+use binaryninja::types::{QualifiedName, Type, TypeArchive};
+use binaryninja::variable::DataVariable;
+
+use binjuice_core::audio::{
+    AudioEventSink, AudioFiles, AudioSink, PendingSound,
+};
+use binjuice_core::cache::SampleCache;
+use binjuice_core::config::Config;
+use binjuice_core::event::{BinJuiceEvent, Event, EventKind};
+use binjuice_core::log::LogLevel;
+use binjuice_core::midi::MidiSink;
+use binjuice_core::osc::OscSink;
+use binjuice_core::output::Output;
+use binjuice_core::profile::{self, DEFAULT_PROFILE};
+use binjuice_core::script::{Script, ScriptSink};
+use binjuice_core::sink::{EventSink, EventSinks, Interest, Mutable};
+use binjuice_core::trace::{TraceSink, TraceView, Tracer};
+
+const LOG_NAME: &str = "BinJuice";
+macro_rules! log_dbg {
+    ($msg:expr) => {
+        #[cfg(debug_assertions)]
+        binaryninja::logger::bn_log(
+            $crate::LOG_NAME,
+            binaryninja::logger::BnLogLevel::DebugLog,
+            $msg,
+        )
+    };
+}
+
+macro_rules! info {
+    ($msg:expr) => {
+        binaryninja::logger::bn_log(
+            $crate::LOG_NAME,
+            binaryninja::logger::BnLogLevel::InfoLog,
+            $msg,
+        )
+    };
+}
+macro_rules! warn {
+    ($msg:expr) => {
+        binaryninja::logger::bn_log(
+            $crate::LOG_NAME,
+            binaryninja::logger::BnLogLevel::WarningLog,
+            $msg,
+        )
+    };
+}
+macro_rules! err {
+    ($msg:expr) => {
+        binaryninja::logger::bn_log(
+            $crate::LOG_NAME,
+            binaryninja::logger::BnLogLevel::ErrorLog,
+            $msg,
+        )
+    };
+}
+
+// the core crate logs through this
+fn core_log(level: LogLevel, msg: &str) {
+    let level = match level {
+        LogLevel::Debug => BnLogLevel::DebugLog,
+        LogLevel::Info => BnLogLevel::InfoLog,
+        LogLevel::Warning => BnLogLevel::WarningLog,
+        LogLevel::Error => BnLogLevel::ErrorLog,
+    };
+    binaryninja::logger::bn_log(LOG_NAME, level, msg);
+}
+
+mod command;
+mod event;
+mod registry;
+mod settings;
+mod watcher;
+use registry::{ViewNotification, ViewRegistry};
+use settings::ViewSettings;
+
+static SOUND_HANDLER: OnceLock<SoundHandler> = OnceLock::new();
+static INITIALIZED: OnceLock<bool> = OnceLock::new();
+static VIEW_REGISTRY: Mutex<ViewRegistry> = Mutex::new(ViewRegistry::new());
+
+pub struct SoundHandler {
+    output: Arc<Output>,
+    audio: RwLock<Arc<AudioFiles>>,
+    // the sounds of the config file, without the user settings, used by the
+    // views that reset an event setting
+    config_audio: RwLock<Arc<AudioFiles>>,
+    // the decoded clips, also used to load the view overrides
+    cache: Mutex<SampleCache>,
+    muted: AtomicBool,
+    // selected with the switch profile command, replaces the config one
+    profile: RwLock<Option<String>>,
+    profiles: RwLock<Vec<String>>,
+    tracer: RwLock<Option<Arc<Tracer>>>,
+    osc: RwLock<Option<Arc<OscSink>>>,
+    midi: RwLock<Option<Arc<MidiSink>>>,
+    script: RwLock<Option<Arc<Script>>>,
+    // incremented on every reload, so the views rebuild their sounds
+    generation: AtomicUsize,
+}
+
+impl std::fmt::Debug for SoundHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SoundHandler").finish()
+    }
+}
+
+impl SoundHandler {
+    // this is called on plugin init, AKA when binja opens
+    pub fn new() -> Result<Self> {
+        let config = Self::read_config()?;
+        let (audio, config_audio, cache) = Self::load_audio(&config, None)?;
+        let output = Output::open(config.device.clone(), &config.fallback)?;
+        let tracer = Self::open_tracer(&config)?;
+        let osc = Self::open_osc(&config);
+        let midi = Self::open_midi(&config, None);
+        let script = Self::open_script(&config).unwrap_or_else(|e| {
+            err!(&format!("Unable to load the script: {e:#}"));
+            None
+        });
+
+        Ok(Self {
+            output: Arc::new(output),
+            audio: RwLock::new(Arc::new(audio)),
+            config_audio: RwLock::new(Arc::new(config_audio)),
+            cache: Mutex::new(cache),
+            muted: AtomicBool::new(false),
+            profile: RwLock::new(None),
+            profiles: RwLock::new(config.profiles.into_keys().collect()),
+            tracer: RwLock::new(tracer),
+            osc: RwLock::new(osc),
+            midi: RwLock::new(midi),
+            script: RwLock::new(script),
+            generation: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn config_path() -> PathBuf {
+        settings::config_path().unwrap_or_else(|| {
+            binaryninja::user_directory().join("binjuice.yml")
+        })
+    }
+
+    /// The event handlers script, next to the config file
+    pub fn script_path() -> PathBuf {
+        Self::config_path().with_file_name("binjuice.rhai")
+    }
+
+    fn packs_dir() -> PathBuf {
+        binaryninja::user_directory().join("binjuice").join("packs")
+    }
+
+    fn read_config() -> Result<Config> {
+        let path = Self::config_path();
+        // the default config file is optional, but a path set in the settings
+        // has to exist
+        let mut config: Config =
+            if !path.exists() && settings::config_path().is_none() {
+                info!("No config file, using the default config");
+                Config::builtin()
+            } else {
+                Config::open(&path)?
+            };
+        config.merge_sounds(&Self::packs_dir())?;
+        Ok(config)
+    }
+
+    fn open_tracer(config: &Config) -> Result<Option<Arc<Tracer>>> {
+        let Some(path) = &config.trace else {
+            return Ok(None);
+        };
+        Ok(Some(Arc::new(Tracer::open(path)?)))
+    }
+
+    // the sounds don't depend on it, so continue without
+    fn open_osc(config: &Config) -> Option<Arc<OscSink>> {
+        let osc = config.osc.as_ref()?;
+        match OscSink::open(osc) {
+            Ok(osc) => Some(Arc::new(osc)),
+            Err(e) => {
+                err!(&format!("Unable to open the OSC output: {e:#}"));
+                None
+            }
+        }
+    }
+
+    // same as OSC, eg: the port can't be created on this system
+    fn open_midi(
+        config: &Config,
+        previous: Option<&MidiSink>,
+    ) -> Option<Arc<MidiSink>> {
+        let midi = config.midi.as_ref()?;
+        match MidiSink::reopen(midi, previous) {
+            Ok(midi) => Some(Arc::new(midi)),
+            Err(e) => {
+                err!(&format!("Unable to open the MIDI output: {e:#}"));
+                None
+            }
+        }
+    }
+
+    fn open_script(config: &Config) -> Result<Option<Arc<Script>>> {
+        let path = Self::script_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let script = Script::load(&path, config.cache)?;
+        info!(&format!("Script loaded from {}", path.display()));
+        Ok(Some(Arc::new(script)))
+    }
+
+    /// Write the MIDI file, if enabled
+    fn save_midi(&self) {
+        let Some(midi) = self.midi.read().unwrap().clone() else {
+            return;
+        };
+        if let Err(e) = midi.save() {
+            err!(&format!("Unable to save the MIDI file: {e:#}"));
+        }
+    }
+
+    fn load_audio(
+        config: &Config,
+        profile: Option<&str>,
+    ) -> Result<(AudioFiles, AudioFiles, SampleCache)> {
+        let profile = profile
+            .or(config.profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE);
+        let files =
+            profile::profile_files(&config.files, &config.profiles, profile)?;
+        log_dbg!(&format!("Using profile {profile}"));
+        let mut cache = SampleCache::new(config.cache);
+        let config_audio = AudioFiles::load(&files, &mut cache)?;
+        // the user settings take priority over the config file
+        let audio = settings::with_overrides(
+            &config_audio,
+            &config_audio,
+            None,
+            &mut cache,
+        )?
+        .unwrap_or_else(|| config_audio.clone());
+        Ok((audio, config_audio, cache))
+    }
+
+    /// Reload the config file, the views pick it up on their next event. On
+    /// error the current config is kept.
+    pub fn reload(&self) -> Result<()> {
+        // the config path may have changed, follow it even if the new config
+        // is invalid, so fixing it is picked up
+        if let Err(e) = watcher::watch_config() {
+            warn!(&format!("Unable to watch the config file: {e}"));
+        }
+        let config = Self::read_config()?;
+        let profile = self.profile.read().unwrap().clone();
+        let (audio, config_audio, cache) =
+            Self::load_audio(&config, profile.as_deref())?;
+        let tracer = Self::open_tracer(&config)?;
+        let osc = Self::open_osc(&config);
+        let previous_midi = self.midi.read().unwrap().clone();
+        let midi = Self::open_midi(&config, previous_midi.as_deref());
+        // while the script is being edited, keep the previous one
+        let script = Self::open_script(&config).unwrap_or_else(|e| {
+            err!(&format!(
+                "Unable to load the script, keeping the previous one: {e:#}"
+            ));
+            self.script.read().unwrap().clone()
+        });
+        // the notes are only kept for the same file, save them otherwise
+        let midi_file = midi.as_ref().and_then(|midi| midi.file());
+        if previous_midi.is_some_and(|previous| previous.file() != midi_file) {
+            self.save_midi();
+        }
+        *self.audio.write().unwrap() = Arc::new(audio);
+        *self.config_audio.write().unwrap() = Arc::new(config_audio);
+        *self.tracer.write().unwrap() = tracer;
+        *self.osc.write().unwrap() = osc;
+        *self.midi.write().unwrap() = midi;
+        *self.script.write().unwrap() = script;
+        *self.cache.lock().unwrap() = cache;
+        self.output.set_device(config.device);
+        *self.profiles.write().unwrap() = config.profiles.into_keys().collect();
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        // the sinks may want other notifications now
+        VIEW_REGISTRY.lock().unwrap().refresh();
+        info!("Config reloaded");
+        Ok(())
+    }
+
+    /// The profiles available at the config, without the default one
+    pub fn profiles(&self) -> Vec<String> {
+        self.profiles.read().unwrap().clone()
+    }
+
+    /// Use the profile instead of the one at the config, until BinJuice is
+    /// restarted. On error the current profile is kept.
+    pub fn switch_profile(&self, profile: String) -> Result<()> {
+        let old = self.profile.write().unwrap().replace(profile);
+        let result = self.reload();
+        if result.is_err() {
+            *self.profile.write().unwrap() = old;
+        }
+        result
+    }
+
+    /// The sounds used outside of a view, eg: start_binary_ninja, None if
+    /// BinJuice is disabled
+    pub fn context(&'static self) -> Option<SoundContext> {
+        let settings = ViewSettings::query(None);
+        settings.enabled.then(|| {
+            let audio = Arc::clone(&self.audio.read().unwrap());
+            SoundContext::new(
+                self,
+                audio,
+                settings.volume,
+                Arc::default(),
+                None,
+            )
+        })
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    /// Mute all the sounds, the sounds already playing are not stopped
+    pub fn set_muted(&self, muted: bool) {
+        self.muted.store(muted, Ordering::Relaxed);
+    }
+}
+
+/// The sounds used for a view, the view settings can override the global ones
+#[derive(Clone)]
+pub struct SoundContext {
+    handler: &'static SoundHandler,
+    audio: Arc<AudioFiles>,
+    volume: f32,
+    // the audio, and the tracer if enabled
+    sinks: EventSinks,
+}
+
+impl SoundContext {
+    fn new(
+        handler: &'static SoundHandler,
+        audio: Arc<AudioFiles>,
+        volume: f32,
+        // shared with the view registration, so it survives a reload
+        muted: Arc<AtomicBool>,
+        view: Option<TraceView>,
+    ) -> Self {
+        let mut sinks = EventSinks::default();
+        // muting stops the sounds, but not the trace
+        let mut register_mutable = |sink: Arc<dyn EventSink>| {
+            let muted = Arc::clone(&muted);
+            sinks.register(Arc::new(Mutable::new(sink, move || {
+                handler.is_muted() || muted.load(Ordering::Relaxed)
+            })));
+        };
+        let output: Arc<dyn AudioSink> = handler.output.clone();
+        register_mutable(Arc::new(AudioEventSink::new(
+            Arc::clone(&audio),
+            output,
+            volume,
+        )));
+        if let Some(osc) = handler.osc.read().unwrap().clone() {
+            register_mutable(osc);
+        }
+        if let Some(midi) = handler.midi.read().unwrap().clone() {
+            register_mutable(midi);
+        }
+        if let Some(script) = handler.script.read().unwrap().clone() {
+            let output: Arc<dyn AudioSink> = handler.output.clone();
+            register_mutable(Arc::new(ScriptSink::new(
+                script,
+                Arc::clone(&audio),
+                output,
+                volume,
+            )));
+        }
+        if let Some(tracer) = handler.tracer.read().unwrap().clone() {
+            sinks.register(Arc::new(TraceSink::new(tracer, view)));
+        }
+        Self {
+            handler,
+            audio,
+            volume,
+            sinks,
+        }
+    }
+
+    /// None if BinJuice is disabled for this view
+    pub fn for_view(
+        handler: &'static SoundHandler,
+        view: &BinaryView,
+        muted: Arc<AtomicBool>,
+    ) -> Option<Self> {
+        let settings = ViewSettings::query(Some(view));
+        if !settings.enabled {
+            return None;
+        }
+        let global = Arc::clone(&handler.audio.read().unwrap());
+        let config_audio = Arc::clone(&handler.config_audio.read().unwrap());
+        let mut cache = handler.cache.lock().unwrap();
+        let overridden = settings::with_overrides(
+            &global,
+            &config_audio,
+            Some(view),
+            &mut cache,
+        );
+        let audio = match overridden {
+            Ok(Some(audio)) => Arc::new(audio),
+            Ok(None) => global,
+            Err(e) => {
+                err!(&format!("Unable to load the view sounds: {e:#}"));
+                global
+            }
+        };
+        let view = Some(event::trace_view(view));
+        Some(Self::new(handler, audio, settings.volume, muted, view))
+    }
+
+    // only decoded if a sink needs the args
+    fn dispatch(
+        &self,
+        kind: EventKind,
+        view: &BinaryView,
+        decode: impl FnOnce() -> BinJuiceEvent,
+    ) {
+        self.sinks.dispatch(kind, || {
+            Event::new(decode(), Some(event::view_info(view)))
+        });
+    }
+
+    /// Send the events sent by BinJuice itself, eg: start_binary_view
+    pub fn play_manual(&self, event: BinJuiceEvent) {
+        self.sinks
+            .dispatch(event.kind(), || Event::new(event, None));
+    }
+
+    /// Play the event sound, ignoring the filter, scheduler and mute
+    pub fn test_play(&self, event: EventKind) {
+        let Some(sound) = self.audio.get(event) else {
+            warn!(&format!("No sound configured for {event}"));
+            return;
+        };
+        let sound = PendingSound::new(event, sound.clone(), self.volume);
+        self.handler.output.play(sound);
+    }
+}
+
+struct AnalysisCompletionEvent;
+impl BinaryViewEventHandler for AnalysisCompletionEvent {
+    // this is called when the auto analysis ends
+    fn on_event(&self, view: &BinaryView) {
+        log_dbg!("InitTrigger called");
+        // keep the lock until registered, so the view is never attached twice
+        let mut registry = VIEW_REGISTRY.lock().unwrap();
+        if registry.contains(view) {
+            warn!("InitTrigger called multiple times on the same BinView");
+            return;
+        }
+
+        // register the sound handler
+        let sound_handler = SOUND_HANDLER
+            .get()
+            .expect("Plugin not initialized correctly");
+
+        match registry.insert(view, sound_handler) {
+            Some(context) => {
+                context.play_manual(BinJuiceEvent::StartBinaryView)
+            }
+            None => info!("BinJuice is disabled for this view"),
+        }
+
+        log_dbg!("InitTrigger registered");
+    }
+}
+
+struct ViewCloseEvent;
+impl BinaryViewEventHandler for ViewCloseEvent {
+    // this is called from the core when the BinaryView is closed, so nothing
+    // here waits for the sounds. The registration keeps the view alive, so
+    // the view destructor would never be called before it's removed.
+    fn on_event(&self, view: &BinaryView) {
+        let Some(registration) = VIEW_REGISTRY.lock().unwrap().remove(view)
+        else {
+            // never registered, eg: closed before the analysis finished
+            return;
+        };
+
+        // the view is going away, so don't rebuild the sounds from it
+        if let Some(context) = registration.current() {
+            context.play_manual(BinJuiceEvent::EndBinaryView);
+        }
+        drop(registration);
+        log_dbg!("Trigger unregistered");
+    }
+}
+
+// how long the exit waits for the end_binary_ninja sound
+const END_SOUND_TIMEOUT: Duration = Duration::from_secs(5);
+
+unsafe extern "C" {
+    fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+}
+
+// there is no shutdown callback for the plugins, so this runs with the exit
+// handlers of the process, both for the UI and headless. The views still open
+// are freed with the process, so their notifications are left registered.
+extern "C" fn end_session() {
+    let Some(sound_handler) = SOUND_HANDLER.get() else {
+        return;
+    };
+    if let Some(context) = sound_handler.context() {
+        context.play_manual(BinJuiceEvent::EndBinaryNinja);
+    }
+    sound_handler.output.drain(END_SOUND_TIMEOUT);
+    if let Err(e) = sound_handler.output.save_recording() {
+        err!(&format!("Unable to save the recording: {e:#}"));
+    }
+    sound_handler.save_midi();
+}
+
+// just copied that from the binary ninja source code
+macro_rules! trait_handler {
+(
+    $(
+        $ffi_param_name:ident => $fun_name:ident(
+            $view_name:ident:
+            $view_raw_type:ty:
+            $view_type:ty =
+            $view_calculated:expr
+            $(,
+                $arg_name:ident:
+                $raw_arg_type:ty:
+                $arg_type:ty =
+                $value_calculated:expr
+            )* $(,)?
+        ) $(-> $ret_type:ty)? $(=> $event:tt)?
+    ),* $(,)?
+) => {
+    impl SoundContext {
+        /// The notifications some sink is interested in
+        pub fn active_triggers(&self) -> Vec<&'static str> {
+            let mut active = vec![];
+            paste! {
+            $(
+            if self.sinks.interest(EventKind::[<$fun_name:camel>]) != Interest::None {
+                active.push(stringify!($fun_name));
+            }
+            )*
+            }
+            active
+        }
+
+        /// Register only the notifications some sink is interested in
+        pub fn triggers(&self) -> DataNotificationTriggers {
+            let mut triggers = DataNotificationTriggers::default();
+            paste! {
+            $(
+            if self.sinks.interest(EventKind::[<$fun_name:camel>]) != Interest::None {
+                triggers = triggers.$fun_name();
+            }
+            )*
+            }
+            triggers
+        }
+    }
+
+    impl CustomDataNotification for ViewNotification {
+        $(
+        // the args not used by the event are still part of the signature
+        #[allow(unused_variables)]
+        fn $fun_name(
+            &mut self,
+            $view_name: $view_type,
+            $($arg_name: $arg_type),*
+        ) $(-> $ret_type)* {
+            if let Some(context) = self.0.context($view_name) {
+                // the variant with the same name, with the fields after =>
+                paste! {
+                    context.dispatch(EventKind::[<$fun_name:camel>], $view_name, || {
+                        BinJuiceEvent::[<$fun_name:camel>] $($event)?
+                    });
+                }
+            }
+            $( <$ret_type as Default>::default() )*
+        }
+        )*
+    }
+};
+}
+trait_handler! {
+    notificationBarrier => notification_barrier(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+    ) -> u64,
+    dataWritten => data_written(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        offset: u64: u64 = offset,
+        len: usize: usize = len,
+    ) => { address: offset, len: len as u64 },
+    dataInserted => data_inserted(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        offset: u64: u64 = offset,
+        len: usize: usize = len,
+    ) => { address: offset, len: len as u64 },
+    dataRemoved => data_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        offset: u64: u64 = offset,
+        len: u64: u64 = len,
+    ) => { address: offset, len },
+    functionAdded => function_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        func: *mut BNFunction: &Function = &Function::from_raw(func),
+    ) => (event::function_info(func)),
+    functionRemoved => function_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        func: *mut BNFunction: &Function = &Function::from_raw(func),
+    ) => (event::function_info(func)),
+    functionUpdated => function_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        func: *mut BNFunction: &Function = &Function::from_raw(func),
+    ) => (event::function_info(func)),
+    functionUpdateRequested => function_update_requested(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        func: *mut BNFunction: &Function = &Function::from_raw(func),
+    ) => (event::function_info(func)),
+    dataVariableAdded => data_variable_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        var: *mut BNDataVariable: &DataVariable = &DataVariable::from_raw(&*var),
+    ) => { address: var.address },
+    dataVariableRemoved => data_variable_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        var: *mut BNDataVariable: &DataVariable = &DataVariable::from_raw(&*var),
+    ) => { address: var.address },
+    dataVariableUpdated => data_variable_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        var: *mut BNDataVariable: &DataVariable = &DataVariable::from_raw(&*var),
+    ) => { address: var.address },
+    dataMetadataUpdated => data_metadata_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        offset: u64: u64 = offset,
+    ) => { address: offset },
+    tagTypeUpdated => tag_type_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        tag_type: *mut BNTagType: &TagType = &TagType{ handle: tag_type },
+    ) => { tag_type: tag_type.name().to_string() },
+    tagAdded => tag_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        tag_ref: *mut BNTagReference: &TagReference = &TagReference::from(&*tag_ref),
+    ) => (event::tag_info(tag_ref)),
+    tagRemoved => tag_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        tag_ref: *mut BNTagReference: &TagReference = &TagReference::from(&*tag_ref),
+    ) => (event::tag_info(tag_ref)),
+    tagUpdated => tag_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        tag_ref: *mut BNTagReference: &TagReference = &TagReference::from(&*tag_ref),
+    ) => (event::tag_info(tag_ref)),
+    symbolAdded => symbol_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        sym: *mut BNSymbol: &Symbol = &Symbol::from_raw(sym),
+    ) => (event::symbol_info(sym)),
+    symbolRemoved => symbol_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        sym: *mut BNSymbol: &Symbol = &Symbol::from_raw(sym),
+    ) => (event::symbol_info(sym)),
+    symbolUpdated => symbol_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        sym: *mut BNSymbol: &Symbol = &Symbol::from_raw(sym),
+    ) => (event::symbol_info(sym)),
+    stringFound => string_found(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        type_: BNStringType: StringType = type_,
+        offset: u64: u64 = offset,
+        len: usize: usize = len,
+    ) => (event::string_info(type_, offset, len)),
+    stringRemoved => string_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        type_: BNStringType: StringType = type_,
+        offset: u64: u64 = offset,
+        len: usize: usize = len,
+    ) => (event::string_info(type_, offset, len)),
+    typeDefined => type_defined(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        name: *mut BNQualifiedName: &QualifiedName = &QualifiedName::from_raw(&*name),
+        type_: *mut BNType: &Type = &Type::from_raw(type_),
+    ) => { name: name.to_string() },
+    typeUndefined => type_undefined(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        name: *mut BNQualifiedName: &QualifiedName = &QualifiedName::from_raw(&*name),
+        type_: *mut BNType: &Type = &Type::from_raw(type_),
+    ) => { name: name.to_string() },
+    typeReferenceChanged => type_reference_changed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        name: *mut BNQualifiedName: &QualifiedName = &QualifiedName::from_raw(&*name),
+        type_: *mut BNType: &Type = &Type::from_raw(type_),
+    ) => { name: name.to_string() },
+    typeFieldReferenceChanged => type_field_reference_changed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        name: *mut BNQualifiedName: &QualifiedName = &QualifiedName::from_raw(&*name),
+        offset: u64: u64 = offset,
+    ) => { name: name.to_string(), offset },
+    segmentAdded => segment_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        segment: *mut BNSegment: &Segment = &Segment::from_raw(segment),
+    ) => (event::segment_info(segment)),
+    segmentRemoved => segment_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        segment: *mut BNSegment: &Segment = &Segment::from_raw(segment),
+    ) => (event::segment_info(segment)),
+    segmentUpdated => segment_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        segment: *mut BNSegment: &Segment = &Segment::from_raw(segment),
+    ) => (event::segment_info(segment)),
+    sectionAdded => section_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        section: *mut BNSection: &Section = &Section::from_raw(section),
+    ) => (event::section_info(section)),
+    sectionRemoved => section_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        section: *mut BNSection: &Section = &Section::from_raw(section),
+    ) => (event::section_info(section)),
+    sectionUpdated => section_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        section: *mut BNSection: &Section = &Section::from_raw(section),
+    ) => (event::section_info(section)),
+    componentNameUpdated => component_name_updated(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        previous_name: *mut c_char: &str = CStr::from_ptr(previous_name).to_str().unwrap(),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+    ),
+    componentAdded => component_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+    ),
+    componentMoved => component_moved(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        former_parent: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(former_parent).unwrap()),
+        new_parent: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(new_parent).unwrap()),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+    ),
+    componentRemoved => component_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        former_parent: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(former_parent).unwrap()),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+    ),
+    componentFunctionAdded => component_function_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+        function: *mut BNFunction: &Function = &Function::from_raw(function),
+    ) => (event::function_info(function)),
+    componentFunctionRemoved => component_function_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+        function: *mut BNFunction: &Function = &Function::from_raw(function),
+    ) => (event::function_info(function)),
+    componentDataVariableAdded => component_data_variable_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+        var: *mut BNDataVariable: &DataVariable = &DataVariable::from_raw(&*var),
+    ) => { address: var.address },
+    componentDataVariableRemoved => component_data_variable_removed(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        component: *mut BNComponent: &Component = &Component::from_raw(NonNull::new(component).unwrap()),
+        var: *mut BNDataVariable: &DataVariable = &DataVariable::from_raw(&*var),
+    ) => { address: var.address },
+    externalLibraryAdded => external_library_added(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        library: *mut BNExternalLibrary: &ExternalLibrary = &ExternalLibrary::from_raw(NonNull::new(library).unwrap()),
+    ),
+    externalLibraryUpdated => external_library_updated(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        library: *mut BNExternalLibrary: &ExternalLibrary = &ExternalLibrary::from_raw(NonNull::new(library).unwrap()),
+    ),
+    externalLibraryRemoved => external_library_removed(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        library: *mut BNExternalLibrary: &ExternalLibrary = &ExternalLibrary::from_raw(NonNull::new(library).unwrap()),
+    ),
+    externalLocationAdded => external_location_added(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        location: *mut BNExternalLocation: &ExternalLocation = &ExternalLocation::from_raw(NonNull::new(location).unwrap()),
+    ),
+    externalLocationUpdated => external_location_updated(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        location: *mut BNExternalLocation: &ExternalLocation = &ExternalLocation::from_raw(NonNull::new(location).unwrap()),
+    ),
+    externalLocationRemoved => external_location_removed(
+        data: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(data),
+        location: *mut BNExternalLocation: &ExternalLocation = &ExternalLocation::from_raw(NonNull::new(location).unwrap()),
+    ),
+    typeArchiveAttached => type_archive_attached(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        id: *const c_char: &str = CStr::from_ptr(id).to_str().unwrap(),
+        path: *const c_char: &[u8] = CStr::from_ptr(path).to_bytes(),
+    ),
+    typeArchiveDetached => type_archive_detached(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        id: *const c_char: &str = CStr::from_ptr(id).to_str().unwrap(),
+        path: *const c_char: &[u8] = CStr::from_ptr(path).to_bytes(),
+    ),
+    typeArchiveConnected => type_archive_connected(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        archive: *mut BNTypeArchive: &TypeArchive = &TypeArchive::from_raw(NonNull::new(archive).unwrap()),
+    ),
+    typeArchiveDisconnected => type_archive_disconnected(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        archive: *mut BNTypeArchive: &TypeArchive = &TypeArchive::from_raw(NonNull::new(archive).unwrap()),
+    ),
+    undoEntryAdded => undo_entry_added(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        entry: *mut BNUndoEntry: &UndoEntry = &UndoEntry::from_raw(NonNull::new(entry).unwrap()),
+    ),
+    undoEntryTaken => undo_entry_taken(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        entry: *mut BNUndoEntry: &UndoEntry = &UndoEntry::from_raw(NonNull::new(entry).unwrap()),
+    ),
+    redoEntryTaken => redo_entry_taken(
+        view: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(view),
+        entry: *mut BNUndoEntry: &UndoEntry = &UndoEntry::from_raw(NonNull::new(entry).unwrap()),
+    ),
+    rebased => rebased(
+        oldview: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(oldview),
+        newview: *mut BNBinaryView: &BinaryView = &BinaryView::from_raw(newview),
+    ),
+}
+
+// The UI calls both CorePluginInit and UIPluginInit, headless only the first,
+// so only the first call does anything.
+fn init() -> bool {
+    *INITIALIZED.get_or_init(init_plugin)
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn CorePluginInit() -> bool {
+    init()
+}
+
+#[allow(non_snake_case)]
+#[unsafe(no_mangle)]
+pub extern "C" fn UIPluginInit() -> bool {
+    init()
+}
+
+fn init_plugin() -> bool {
+    binaryninja::tracing_init!(LOG_NAME);
+
+    // create the logger, so it shows up at the log "filter" dropdown
+    let _ = binaryninja::logger::Logger::new(LOG_NAME);
+    binjuice_core::log::set_logger(core_log);
+
+    // the settings are used to find the config file, register them first
+    settings::register_settings();
+
+    let handler = match SoundHandler::new() {
+        Ok(handler) => handler,
+        Err(err) => {
+            err!(&format!("Unable init BinJuice sound handler: {err}"));
+            return false;
+        }
+    };
+
+    SOUND_HANDLER
+        .set(handler)
+        .expect("BinJuice was initialized multiple times");
+    let sound_handler = SOUND_HANDLER.get().unwrap();
+    if let Some(context) = sound_handler.context() {
+        context.play_manual(BinJuiceEvent::StartBinaryNinja);
+    }
+    binaryninja::binary_view::register_binary_view_event(
+        BinaryViewEventType::BinaryViewInitialAnalysisCompletionEvent,
+        AnalysisCompletionEvent,
+    );
+    binaryninja::binary_view::register_binary_view_event(
+        BinaryViewEventType::BinaryViewFinalizationEvent,
+        ViewCloseEvent,
+    );
+    // SAFETY: the callback only uses our own statics
+    if unsafe { atexit(end_session) } != 0 {
+        warn!("Unable to register the exit handler");
+    }
+    command::register_commands();
+    if let Err(e) = watcher::watch_config() {
+        warn!(&format!("Unable to watch the config file: {e}"));
+    }
+    if let Err(e) = sound_handler.output.monitor() {
+        warn!(&format!("Unable to monitor the audio devices: {e}"));
+    }
+
+    true
+}
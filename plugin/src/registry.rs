@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use binaryninja::binary_view::{BinaryView, BinaryViewExt};
+use binaryninja::data_notification::{
+    CustomDataNotification, DataNotificationHandle,
+};
+use binaryninja::rc::Ref;
+
+use crate::{SoundContext, SoundHandler};
+
+/// The sounds of a registered view. It doesn't keep the view alive, so the
+/// context is rebuilt from the view given by the next notification or command
+/// if the config changed since it was built.
+pub struct ViewSlot {
+    handler: &'static SoundHandler,
+    muted: Arc<AtomicBool>,
+    // the handler generation it was built for, None if BinJuice is disabled
+    // for the view
+    context: RwLock<(usize, Option<Arc<SoundContext>>)>,
+}
+
+impl ViewSlot {
+    fn new(view: &BinaryView, handler: &'static SoundHandler) -> Self {
+        let slot = Self {
+            handler,
+            muted: Arc::default(),
+            context: RwLock::new((0, None)),
+        };
+        slot.rebuild(view);
+        slot
+    }
+
+    fn rebuild(&self, view: &BinaryView) -> Option<Arc<SoundContext>> {
+        let generation = self.handler.generation();
+        let context =
+            SoundContext::for_view(self.handler, view, Arc::clone(&self.muted))
+                .map(Arc::new);
+        *self.context.write().unwrap() = (generation, context.clone());
+        context
+    }
+
+    /// The sounds for the current config, None if BinJuice is disabled
+    pub fn context(&self, view: &BinaryView) -> Option<Arc<SoundContext>> {
+        {
+            let (generation, context) = &*self.context.read().unwrap();
+            if *generation == self.handler.generation() {
+                return context.clone();
+            }
+        }
+        self.rebuild(view)
+    }
+
+    /// The sounds last used, even if the config changed since
+    pub fn current(&self) -> Option<Arc<SoundContext>> {
+        self.context.read().unwrap().1.clone()
+    }
+}
+
+/// The notification registered for each view
+pub struct ViewNotification(pub Arc<ViewSlot>);
+
+// the DataNotification registration for a BinaryView, dropping it will
+// unregister the notification. It keeps a reference to the view, to register
+// again after a reload, and so does the handle, to unregister from it. So the
+// registration is removed when the view is closed, not when it's destroyed.
+pub struct ViewRegistration {
+    view: Ref<BinaryView>,
+    slot: Arc<ViewSlot>,
+    // None if no sink is interested in any notification, eg: BinJuice is
+    // disabled for the view
+    handle: Option<DataNotificationHandle<'static, ViewNotification>>,
+}
+
+impl ViewRegistration {
+    fn new(view: &BinaryView, handler: &'static SoundHandler) -> Self {
+        let slot = Arc::new(ViewSlot::new(view, handler));
+        let mut registration = Self {
+            view: view.to_owned(),
+            slot,
+            handle: None,
+        };
+        registration.register();
+        registration
+    }
+
+    // only the notifications the sinks are interested in
+    fn register(&mut self) {
+        // unregister first, so no notification is received twice
+        self.handle = None;
+        let Some(context) = self.slot.current() else {
+            return;
+        };
+        if context.active_triggers().is_empty() {
+            return;
+        }
+        let notification = ViewNotification(Arc::clone(&self.slot));
+        self.handle =
+            Some(notification.register(&self.view, context.triggers()));
+    }
+
+    // rebuild the sounds for the reloaded config, and register the
+    // notifications they need
+    fn refresh(&mut self) {
+        self.slot.rebuild(&self.view);
+        self.register();
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.slot.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.slot.muted.store(muted, Ordering::Relaxed);
+    }
+
+    pub fn context(&self, view: &BinaryView) -> Option<Arc<SoundContext>> {
+        self.slot.context(view)
+    }
+
+    pub fn current(&self) -> Option<Arc<SoundContext>> {
+        self.slot.current()
+    }
+}
+
+// SAFETY: the handle is a reference to the view, that is Send like ours, and
+// the raw notification struct, whose context points to the boxed
+// ViewNotification. The core calls the notification from any thread, so it
+// only holds the Send and Sync ViewSlot, and unregistering on drop is also
+// allowed from any thread.
+unsafe impl Send for ViewRegistration {}
+
+// The handle address alone is not enough to identify a view, once a view is
+// closed the allocator is free to give the same address to the next one. The
+// session id is unique for each opened file and never reused, so the pair is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ViewId {
+    session: usize,
+    handle: usize,
+}
+
+impl ViewId {
+    pub fn new(view: &BinaryView) -> Self {
+        Self {
+            session: view.file().session_id(),
+            handle: view.handle as usize,
+        }
+    }
+}
+
+/// Owns the notification registration of every view we are attached to
+pub struct ViewRegistry {
+    views: BTreeMap<ViewId, ViewRegistration>,
+}
+
+impl ViewRegistry {
+    pub const fn new() -> Self {
+        Self {
+            views: BTreeMap::new(),
+        }
+    }
+
+    pub fn contains(&self, view: &BinaryView) -> bool {
+        self.views.contains_key(&ViewId::new(view))
+    }
+
+    pub fn get(&self, view: &BinaryView) -> Option<&ViewRegistration> {
+        self.views.get(&ViewId::new(view))
+    }
+
+    /// returns the sounds for the view, None if BinJuice is disabled for it
+    pub fn insert(
+        &mut self,
+        view: &BinaryView,
+        handler: &'static SoundHandler,
+    ) -> Option<Arc<SoundContext>> {
+        let id = ViewId::new(view);
+        let registration = ViewRegistration::new(view, handler);
+        let context = registration.current();
+        let old = self.views.insert(id, registration);
+        debug_assert!(old.is_none(), "View registered multiple times");
+        context
+    }
+
+    /// remove the view registration, the notification is unregistered and
+    /// the view released once the returned value is dropped
+    pub fn remove(&mut self, view: &BinaryView) -> Option<ViewRegistration> {
+        self.views.remove(&ViewId::new(view))
+    }
+
+    /// Rebuild the sounds of every view after a reload, and register the
+    /// notifications they need now
+    pub fn refresh(&mut self) {
+        for registration in self.views.values_mut() {
+            registration.refresh();
+        }
+    }
+}
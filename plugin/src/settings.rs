@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use serde_json::json;
+
+use binaryninja::binary_view::BinaryView;
+use binaryninja::settings::{QueryOptions, Settings};
+
+use binjuice_core::audio::AudioFiles;
+use binjuice_core::cache::SampleCache;
+use binjuice_core::event::EventKind;
+use binjuice_core::playback::{Sound, SoundConfig};
+
+const GROUP: &str = "binjuice";
+const ENABLED: &str = "binjuice.enabled";
+const VOLUME: &str = "binjuice.volume";
+const CONFIG_PATH: &str = "binjuice.configPath";
+// the event setting value that uses the config file entry, eg: at a project
+// to replace a user setting, an empty value is the same as unset
+const USE_CONFIG: &str = "default";
+
+fn event_key(event: &str) -> String {
+    format!("{GROUP}.events.{event}")
+}
+
+pub fn register_settings() {
+    let settings = Settings::new();
+    settings.register_group(GROUP, "BinJuice");
+    let register = |key: &str, properties: serde_json::Value| {
+        if !settings.register_setting_json(key, &properties.to_string()) {
+            err!(&format!("Unable to register the setting {key}"));
+        }
+    };
+
+    register(
+        ENABLED,
+        json!({
+            "title": "Enable",
+            "type": "boolean",
+            "default": true,
+            "description": "Play sounds for the Binary Ninja events. Applied \
+                when a file is opened, or when the config is reloaded.",
+        }),
+    );
+    register(
+        VOLUME,
+        json!({
+            "title": "Master Volume",
+            "type": "number",
+            "default": 1.0,
+            "minValue": 0.0,
+            "maxValue": 4.0,
+            "description": "Volume multiplier applied to all the sounds. \
+                Applied when a file is opened, or when the config is \
+                reloaded.",
+        }),
+    );
+    // the config file is global, so it can only be set by the user
+    register(
+        CONFIG_PATH,
+        json!({
+            "title": "Config File",
+            "type": "string",
+            "default": "",
+            "description": "Path to the BinJuice config file, if empty \
+                binjuice.yml at the user directory is used. Reload the \
+                config to use it.",
+            "ignore": ["SettingsProjectScope", "SettingsResourceScope"],
+            "uiSelectionAction": "file",
+        }),
+    );
+    for event in EventKind::ALL {
+        register(
+            &event_key(event.name()),
+            json!({
+                "title": format!("Sound for {event}"),
+                "type": "string",
+                "default": "",
+                "description": format!(
+                    "File played on {event}, replaces the file of the config \
+                    entry and keeps its other options. Set it to \
+                    {USE_CONFIG} to use the config entry instead of the user \
+                    setting. Applied when a file is opened, or when the \
+                    config is reloaded."
+                ),
+                "uiSelectionAction": "file",
+            }),
+        );
+    }
+}
+
+/// The settings that can be changed for each view
+pub struct ViewSettings {
+    pub enabled: bool,
+    pub volume: f32,
+}
+
+impl ViewSettings {
+    /// without a view only the user settings are used
+    pub fn query(view: Option<&BinaryView>) -> Self {
+        let settings = Settings::new();
+        let Some(view) = view else {
+            return Self {
+                enabled: settings.get_bool(ENABLED),
+                volume: settings.get_double(VOLUME) as f32,
+            };
+        };
+        let mut opts = QueryOptions::new_with_view(view);
+        Self {
+            enabled: settings.get_bool_with_opts(ENABLED, &mut opts),
+            volume: settings.get_double_with_opts(VOLUME, &mut opts) as f32,
+        }
+    }
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    let path = Settings::new().get_string(CONFIG_PATH).to_string();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// The sound set for an event
+#[derive(PartialEq)]
+enum EventSetting {
+    /// the config file entry
+    Config,
+    File(PathBuf),
+}
+
+impl EventSetting {
+    // None if unset
+    fn parse(value: String) -> Option<Self> {
+        match value.as_str() {
+            "" => None,
+            USE_CONFIG => Some(Self::Config),
+            _ => Some(Self::File(PathBuf::from(value))),
+        }
+    }
+}
+
+/// The sound set for the event, if it differs from what was already loaded.
+/// For a view that is the user setting, otherwise the config file.
+fn event_override(
+    event: EventKind,
+    view: Option<&BinaryView>,
+) -> Option<EventSetting> {
+    let settings = Settings::new();
+    let key = event_key(event.name());
+    let user = EventSetting::parse(settings.get_string(&key).to_string())
+        .unwrap_or(EventSetting::Config);
+    let Some(view) = view else {
+        return (user != EventSetting::Config).then_some(user);
+    };
+    let mut opts = QueryOptions::new_with_view(view);
+    let setting = EventSetting::parse(
+        settings.get_string_with_opts(&key, &mut opts).to_string(),
+    )?;
+    (setting != user).then_some(setting)
+}
+
+/// Replace the files set by the settings, None if nothing changed. `config`
+/// are the sounds of the config file, without the user settings.
+pub fn with_overrides(
+    audio: &AudioFiles,
+    config: &AudioFiles,
+    view: Option<&BinaryView>,
+    cache: &mut SampleCache,
+) -> Result<Option<AudioFiles>> {
+    let mut overridden = None;
+    for &event in EventKind::ALL {
+        let path = match event_override(event, view) {
+            None => continue,
+            Some(EventSetting::File(path)) => path,
+            Some(EventSetting::Config) => {
+                let audio = overridden.get_or_insert_with(|| audio.clone());
+                match config.get(event) {
+                    Some(sound) => audio.set(event, sound.clone()),
+                    None => audio.remove(event),
+                }
+                continue;
+            }
+        };
+        let config = SoundConfig::Path(path);
+        // only the file changes, the params, filter and scheduling are kept
+        let sound = match audio.get(event) {
+            Some(sound) => config.load_pool(cache).map(|pool| Sound {
+                pool: Arc::new(pool),
+                ..sound.clone()
+            }),
+            None => Sound::load(&config, cache),
+        }
+        .with_context(|| format!("Invalid setting for {event}"))?;
+        overridden
+            .get_or_insert_with(|| audio.clone())
+            .set(event, sound);
+    }
+    Ok(overridden)
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{SOUND_HANDLER, SoundHandler};
+
+// editors usually produce a burst of events on save, wait for things to settle
+// before reloading
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+struct ConfigWatcher {
+    watcher: RecommendedWatcher,
+    dir: PathBuf,
+}
+
+static WATCHER: Mutex<Option<ConfigWatcher>> = Mutex::new(None);
+
+// watch the directory instead of the file, editors often save by replacing
+// the file, what would end the watch of the file itself
+fn config_dir() -> Result<PathBuf> {
+    Ok(SoundHandler::config_path()
+        .parent()
+        .context("Config file without a parent directory")?
+        .to_owned())
+}
+
+/// Reload the config every time the file, or the script next to it, changes.
+/// Called again on reload, to follow a new config path.
+pub fn watch_config() -> Result<()> {
+    let dir = config_dir()?;
+    let mut current = WATCHER.lock().unwrap();
+    match &mut *current {
+        Some(watcher) if watcher.dir == dir => {}
+        Some(watcher) => {
+            if let Err(e) = watcher.watcher.unwatch(&watcher.dir) {
+                log_dbg!(&format!("Unable to stop the config watch: {e}"));
+            }
+            watcher.watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            watcher.dir = dir;
+        }
+        None => {
+            let (tx, rx) = mpsc::channel();
+            let mut watcher = notify::recommended_watcher(tx)?;
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            std::thread::Builder::new()
+                .name("BinJuice config watcher".to_owned())
+                .spawn(move || watch_thread(rx))?;
+            *current = Some(ConfigWatcher { watcher, dir });
+        }
+    }
+    Ok(())
+}
+
+// the paths are read every time, they can change on reload
+fn is_watched(path: &Path) -> bool {
+    let name = path.file_name();
+    name == SoundHandler::config_path().file_name()
+        || name == SoundHandler::script_path().file_name()
+}
+
+fn is_relevant(event: &notify::Result<notify::Event>) -> bool {
+    match event {
+        Ok(event) if event.kind.is_access() => false,
+        Ok(event) => event.paths.iter().any(|path| is_watched(path)),
+        Err(e) => {
+            err!(&format!("Config watcher error: {e}"));
+            false
+        }
+    }
+}
+
+fn watch_thread(rx: Receiver<notify::Result<notify::Event>>) {
+    while let Ok(event) = rx.recv() {
+        if !is_relevant(&event) {
+            continue;
+        }
+        // only the watched files extend the wait, other files in the
+        // directory may never stop changing, eg: a trace
+        let mut deadline = Instant::now() + SETTLE_TIME;
+        loop {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(wait) {
+                Ok(event) if is_relevant(&event) => {
+                    deadline = Instant::now() + SETTLE_TIME;
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let Some(sound_handler) = SOUND_HANDLER.get() else {
+            continue;
+        };
+        log_dbg!("Config file changed, reloading");
+        if let Err(e) = sound_handler.reload() {
+            err!(&format!("Unable to reload the config: {e}"));
+        }
+    }
+}